@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+use crate::domain::{Point, Segment, SweepLineProblem, distance};
+use crate::noding::{node_segments, NODE_TOLERANCE};
+use crate::solvers::sweep_line_solver;
+
+#[derive(Clone, Debug)]
+pub struct Vertex {
+    pub point: Point,
+    // Any half-edge leaving the vertex, None for isolated points
+    pub incident_edge: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HalfEdge {
+    pub origin: usize,
+    pub twin: usize,
+    pub next: usize,
+    pub prev: usize,
+    // The face lies to the left of the half-edge
+    pub face: usize,
+    // Index of the input segment this piece comes from
+    pub segment: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Face {
+    // Half-edge of the outer boundary cycle, None for the unbounded face
+    pub outer_component: Option<usize>,
+    // One half-edge for each hole cycle inside the face
+    pub inner_components: Vec<usize>,
+}
+
+// Doubly-connected edge list of the planar arrangement induced by a set of segments.
+// Face 0 is always the unbounded face.
+#[derive(Clone, Debug)]
+pub struct Arrangement {
+    pub vertices: Vec<Vertex>,
+    pub half_edges: Vec<HalfEdge>,
+    pub faces: Vec<Face>,
+}
+
+fn signed_area(points: &[&Point]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let p = points[i];
+        let q = points[(i + 1) % points.len()];
+        area += p.x * q.y - q.x * p.y;
+    }
+    area / 2.0
+}
+
+fn point_in_cycle(p: &Point, points: &[&Point]) -> bool {
+    let mut inside = false;
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        if (a.y > p.y) != (b.y > p.y) {
+            let x = a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y);
+            if p.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+impl Arrangement {
+
+    // Builds the arrangement from a problem whose `result` and `result_pairs` have
+    // already been filled by one of the solvers
+    pub fn build(problem: &SweepLineProblem) -> Arrangement {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut vertex_ids = HashMap::new();
+        let mut edges = Vec::new();
+        let mut seen_edges = HashSet::new();
+        // Pieces that meet share exactly the same node coordinates
+        for piece in node_segments(problem) {
+            let [a, b] = [piece.segment.ini, piece.segment.end].map(|point| {
+                *vertex_ids.entry((point.x.to_bits(), point.y.to_bits())).or_insert_with(|| {
+                    vertices.push(Vertex { point, incident_edge: None });
+                    vertices.len() - 1
+                })
+            });
+            // Overlapping segments produce the same piece more than once
+            if seen_edges.insert((a.min(b), a.max(b))) {
+                edges.push((a, b, piece.original));
+            }
+        }
+
+        let mut half_edges = Vec::new();
+        for &(a, b, segment) in edges.iter() {
+            let h = half_edges.len();
+            half_edges.push(HalfEdge { origin: a, twin: h + 1, next: 0, prev: 0, face: 0, segment });
+            half_edges.push(HalfEdge { origin: b, twin: h, next: 0, prev: 0, face: 0, segment });
+        }
+
+        // Outgoing half-edges around each vertex in counter-clockwise order
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); vertices.len()];
+        for (h, half_edge) in half_edges.iter().enumerate() {
+            outgoing[half_edge.origin].push(h);
+        }
+        for (v, around) in outgoing.iter_mut().enumerate() {
+            let origin = &vertices[v].point;
+            around.sort_by(|&a, &b| {
+                let pa = &vertices[half_edges[half_edges[a].twin].origin].point;
+                let pb = &vertices[half_edges[half_edges[b].twin].origin].point;
+                let angle_a = (pa.y - origin.y).atan2(pa.x - origin.x);
+                let angle_b = (pb.y - origin.y).atan2(pb.x - origin.x);
+                angle_a.partial_cmp(&angle_b).unwrap()
+            });
+            vertices[v].incident_edge = around.first().copied();
+        }
+        // The half-edge after one arriving at v is the outgoing one right before its twin
+        // in counter-clockwise order, this keeps every face on the left side
+        for around in outgoing.iter() {
+            for k in 0..around.len() {
+                let incoming = half_edges[around[k]].twin;
+                let next = around[(k + around.len() - 1) % around.len()];
+                half_edges[incoming].next = next;
+                half_edges[next].prev = incoming;
+            }
+        }
+
+        let mut arrangement = Arrangement {
+            vertices,
+            half_edges,
+            faces: vec![Face { outer_component: None, inner_components: Vec::new() }],
+        };
+
+        let mut cycle_of = vec![usize::MAX; arrangement.half_edges.len()];
+        let mut cycles = Vec::new();
+        for h in 0..arrangement.half_edges.len() {
+            if cycle_of[h] != usize::MAX {
+                continue;
+            }
+            for e in arrangement.cycle(h) {
+                cycle_of[e] = cycles.len();
+            }
+            cycles.push(h);
+        }
+
+        // Counter-clockwise cycles bound a face, the rest are holes of some other face.
+        // Cycles around trees enclose nothing but rounding can leave them a tiny area.
+        let mut holes = Vec::new();
+        for &h in cycles.iter() {
+            let points = arrangement.cycle_points(h);
+            let perimeter: f64 = (0..points.len())
+                .map(|i| distance(points[i], points[(i + 1) % points.len()]))
+                .sum();
+            if signed_area(&points) > NODE_TOLERANCE * perimeter {
+                arrangement.faces.push(Face { outer_component: Some(h), inner_components: Vec::new() });
+            } else {
+                holes.push(h);
+            }
+        }
+        for &h in holes.iter() {
+            let point = &arrangement.vertices[arrangement.half_edges[h].origin].point;
+            let mut container = 0;
+            let mut container_area = f64::INFINITY;
+            for f in 1..arrangement.faces.len() {
+                let outer = arrangement.faces[f].outer_component.unwrap();
+                let points = arrangement.cycle_points(outer);
+                let area = signed_area(&points);
+                if area < container_area && point_in_cycle(point, &points) {
+                    container = f;
+                    container_area = area;
+                }
+            }
+            arrangement.faces[container].inner_components.push(h);
+        }
+
+        for f in 0..arrangement.faces.len() {
+            let face = &arrangement.faces[f];
+            let mut boundary = face.inner_components.clone();
+            boundary.extend(face.outer_component);
+            for h in boundary {
+                for e in arrangement.cycle(h) {
+                    arrangement.half_edges[e].face = f;
+                }
+            }
+        }
+        arrangement
+    }
+
+    // Half-edges of the boundary cycle that contains `start`
+    pub fn cycle(&self, start: usize) -> Vec<usize> {
+        let mut cycle = vec![start];
+        let mut current = self.half_edges[start].next;
+        while current != start {
+            cycle.push(current);
+            current = self.half_edges[current].next;
+        }
+        cycle
+    }
+
+    fn cycle_points(&self, start: usize) -> Vec<&Point> {
+        self.cycle(start).iter()
+            .map(|&h| &self.vertices[self.half_edges[h].origin].point)
+            .collect()
+    }
+
+    pub fn faces(&self) -> impl Iterator<Item = (usize, &Face)> {
+        self.faces.iter().enumerate()
+    }
+
+    // Area of the face without its holes, infinite for the unbounded face
+    pub fn face_area(&self, face: usize) -> f64 {
+        let face = &self.faces[face];
+        let Some(outer) = face.outer_component else { return f64::INFINITY };
+        let mut area = signed_area(&self.cycle_points(outer));
+        for &hole in face.inner_components.iter() {
+            area += signed_area(&self.cycle_points(hole));
+        }
+        area
+    }
+
+    // Polygon of the outer boundary of the face, empty for the unbounded face
+    pub fn face_boundary(&self, face: usize) -> Vec<Point> {
+        match self.faces[face].outer_component {
+            Some(outer) => self.cycle_points(outer).into_iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+pub fn test_arrangement() {
+    let segment = |x1: f64, y1: f64, x2: f64, y2: f64| Segment {
+        ini: Point { x: x1, y: y1 },
+        end: Point { x: x2, y: y2 },
+    };
+
+    // Square with both diagonals, four triangles
+    let mut problem = SweepLineProblem::new(vec![
+        segment(0.0, 0.0, 10.0, 0.0),
+        segment(10.0, 0.0, 10.0, 10.0),
+        segment(10.0, 10.0, 0.0, 10.0),
+        segment(0.0, 10.0, 0.0, 0.0),
+        segment(0.0, 0.0, 10.0, 10.0),
+        segment(0.0, 10.0, 10.0, 0.0),
+    ]);
+    sweep_line_solver(&mut problem);
+    let arrangement = Arrangement::build(&problem);
+    assert_eq!(arrangement.vertices.len(), 5);
+    assert_eq!(arrangement.half_edges.len(), 16);
+    assert_eq!(arrangement.faces.len(), 5);
+    for (f, _) in arrangement.faces().skip(1) {
+        assert!((arrangement.face_area(f) - 25.0).abs() < 1e-9);
+    }
+
+    // A small square floating inside a big one is a hole of the big face
+    let mut problem = SweepLineProblem::new(vec![
+        segment(0.0, 0.0, 10.0, 0.0),
+        segment(10.0, 0.0, 10.0, 10.0),
+        segment(10.0, 10.0, 0.0, 10.0),
+        segment(0.0, 10.0, 0.0, 0.0),
+        segment(4.0, 4.0, 6.0, 4.0),
+        segment(6.0, 4.0, 6.0, 6.0),
+        segment(6.0, 6.0, 4.0, 6.0),
+        segment(4.0, 6.0, 4.0, 4.0),
+    ]);
+    sweep_line_solver(&mut problem);
+    let arrangement = Arrangement::build(&problem);
+    assert_eq!(arrangement.faces.len(), 3);
+    let mut areas: Vec<f64> = arrangement.faces().skip(1)
+        .map(|(f, _)| arrangement.face_area(f))
+        .collect();
+    areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert!((areas[0] - 4.0).abs() < 1e-9);
+    assert!((areas[1] - 96.0).abs() < 1e-9);
+    assert_eq!(arrangement.faces[0].inner_components.len(), 1);
+
+    // Euler's formula holds for every random arrangement: V - E + F = 1 + components
+    for _ in 0..100 {
+        let mut problem = SweepLineProblem::new((0..10).map(|_| segment(
+            (rand::random::<u32>() % 100) as f64, (rand::random::<u32>() % 100) as f64,
+            (rand::random::<u32>() % 100) as f64, (rand::random::<u32>() % 100) as f64,
+        )).collect());
+        crate::solvers::naive_intersection_solver(&mut problem);
+        let arrangement = Arrangement::build(&problem);
+        let connected_vertices = arrangement.vertices.iter()
+            .filter(|v| v.incident_edge.is_some()).count() as i64;
+        let components = arrangement.faces.iter()
+            .map(|face| face.inner_components.len()).sum::<usize>() as i64;
+        assert_eq!(
+            connected_vertices - (arrangement.half_edges.len() / 2) as i64
+                + arrangement.faces.len() as i64,
+            1 + components
+        );
+    }
+}
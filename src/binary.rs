@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::time::Instant;
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem};
+use crate::example_generator::create_seeded_example;
+
+// Binary problem file, little-endian:
+//   "SWLB", version (u32), flags (u32), number of segments (u64)
+//   x1 y1 x2 y2 (f64) for every segment
+//   one color byte per segment (0 red, 1 blue) if the colors flag is set
+// Labels, tags, metadata and expected results are not stored.
+const MAGIC: &[u8; 4] = b"SWLB";
+pub const BINARY_FILE_VERSION: u32 = 1;
+const FLAG_COLORS: u32 = 1;
+const HEADER_SIZE: usize = 4 + 4 + 4 + 8;
+const SEGMENT_SIZE: usize = 4 * 8;
+// Segments decoded per read, so the loader never holds more than one chunk of raw bytes
+const CHUNK_SEGMENTS: usize = 64 * 1024;
+
+pub fn is_binary_path(path: &str) -> bool {
+    path.ends_with(".swlb")
+}
+
+// Reads the segments of a binary file a chunk at a time
+pub struct BinaryReader<R: Read> {
+    reader: R,
+    pub n_segments: u64,
+    pub has_colors: bool,
+    remaining: u64,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> BinaryReader<R> {
+    pub fn new(mut reader: R) -> Result<BinaryReader<R>, String> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(|_| "file too short for the header".to_string())?;
+        if &header[0..4] != MAGIC {
+            return Err("not a binary problem file".to_string());
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != BINARY_FILE_VERSION {
+            return Err(format!("unsupported binary file version {}", version));
+        }
+        let flags = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let n_segments = u64::from_le_bytes(header[12..20].try_into().unwrap());
+        Ok(BinaryReader {
+            reader,
+            n_segments,
+            has_colors: flags & FLAG_COLORS != 0,
+            remaining: n_segments,
+            buffer: Vec::new(),
+        })
+    }
+
+    // Appends the next chunk of segments, returns how many were read, 0 at the end
+    pub fn read_chunk(&mut self, segments: &mut Vec<Segment>) -> Result<usize, String> {
+        let count = self.remaining.min(CHUNK_SEGMENTS as u64) as usize;
+        self.buffer.resize(count * SEGMENT_SIZE, 0);
+        self.reader.read_exact(&mut self.buffer).map_err(|_| {
+            format!("file ends before the {} announced segments", self.n_segments)
+        })?;
+        segments.extend(self.buffer.chunks_exact(SEGMENT_SIZE).map(|bytes| {
+            let value = |i: usize| f64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap());
+            Segment { ini: Point { x: value(0), y: value(1) }, end: Point { x: value(2), y: value(3) } }
+        }));
+        self.remaining -= count as u64;
+        Ok(count)
+    }
+
+    // Must be called once every segment has been read
+    pub fn read_colors(&mut self) -> Result<Vec<SegmentColor>, String> {
+        if !self.has_colors {
+            return Ok(vec![SegmentColor::Red; self.n_segments as usize]);
+        }
+        let mut bytes = vec![0u8; self.n_segments as usize];
+        self.reader.read_exact(&mut bytes).map_err(|_| "file ends before the colors".to_string())?;
+        bytes.iter().map(|&byte| match byte {
+            0 => Ok(SegmentColor::Red),
+            1 => Ok(SegmentColor::Blue),
+            _ => Err(format!("unknown color {}", byte)),
+        }).collect()
+    }
+}
+
+pub fn load_binary(path: &str) -> Result<SweepLineProblem, String> {
+    let file = File::open(path).map_err(|error| format!("{}: {}", path, error))?;
+    let size = file.metadata().map_err(|error| format!("{}: {}", path, error))?.len();
+    let mut reader = BinaryReader::new(BufReader::new(file))
+        .map_err(|error| format!("{}: {}", path, error))?;
+    // Checked before allocating, a corrupted count must not reserve gigabytes
+    let needed = reader.n_segments
+        .checked_mul(SEGMENT_SIZE as u64 + reader.has_colors as u64)
+        .and_then(|bytes| bytes.checked_add(HEADER_SIZE as u64));
+    if needed != Some(size) {
+        return Err(format!("{}: size does not match {} segments", path, reader.n_segments));
+    }
+
+    let mut segments = Vec::with_capacity(reader.n_segments as usize);
+    while reader.read_chunk(&mut segments).map_err(|error| format!("{}: {}", path, error))? > 0 {}
+    let colors = reader.read_colors().map_err(|error| format!("{}: {}", path, error))?;
+    let mut problem = SweepLineProblem::new(segments);
+    for (info, color) in problem.segment_info.iter_mut().zip(colors) {
+        info.color = color;
+    }
+    Ok(problem)
+}
+
+pub fn save_binary(problem: &SweepLineProblem, path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+    let mut writer = BufWriter::new(file);
+    let has_colors = problem.segment_info.iter().any(|info| info.color != SegmentColor::Red);
+    let mut bytes = Vec::with_capacity(HEADER_SIZE);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&BINARY_FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(if has_colors { FLAG_COLORS } else { 0 }).to_le_bytes());
+    bytes.extend_from_slice(&(problem.segments.len() as u64).to_le_bytes());
+    writer.write_all(&bytes).map_err(|error| error.to_string())?;
+    for segment in problem.segments.iter() {
+        for value in [segment.ini.x, segment.ini.y, segment.end.x, segment.end.y] {
+            writer.write_all(&value.to_le_bytes()).map_err(|error| error.to_string())?;
+        }
+    }
+    if has_colors {
+        let colors: Vec<u8> = problem.segment_info.iter()
+            .map(|info| match info.color { SegmentColor::Red => 0, SegmentColor::Blue => 1 })
+            .collect();
+        writer.write_all(&colors).map_err(|error| error.to_string())?;
+    }
+    writer.flush().map_err(|error| error.to_string())
+}
+
+// Text to binary or binary to text, the format of each file is given by its extension
+pub fn convert(input: &str, output: &str) -> Result<(), String> {
+    let problem = if is_binary_path(input) {
+        load_binary(input)?
+    } else {
+        SweepLineProblem::load(input)?
+    };
+    if is_binary_path(output) {
+        save_binary(&problem, output)
+    } else {
+        problem.save(output)
+    }
+}
+
+pub fn test_binary() {
+    let directory = std::env::temp_dir();
+    let binary_path = directory.join("sweep_line_test.swlb");
+    let binary_path = binary_path.to_str().unwrap();
+    let text_path = directory.join("sweep_line_test.txt");
+    let text_path = text_path.to_str().unwrap();
+
+    // More segments than a chunk, so the reader goes through several of them
+    let problem = create_seeded_example(CHUNK_SEGMENTS as i32 + 10, 7);
+    save_binary(&problem, binary_path).unwrap();
+    let loaded = load_binary(binary_path).unwrap();
+    assert!(loaded.segments == problem.segments);
+    assert!(loaded.segment_info == problem.segment_info);
+
+    let mut problem = SweepLineProblem::load("problems/sweep_line_problem_16.txt").unwrap();
+    convert("problems/sweep_line_problem_16.txt", binary_path).unwrap();
+    convert(binary_path, text_path).unwrap();
+    let converted = SweepLineProblem::load(text_path).unwrap();
+    assert!(converted.segments == problem.segments);
+    // Only the colors survive the binary format
+    for info in problem.segment_info.iter_mut() {
+        *info = SegmentInfo::colored(info.color);
+    }
+    assert!(converted.segment_info == problem.segment_info);
+
+    let mut bytes = std::fs::read(binary_path).unwrap();
+    bytes.truncate(bytes.len() - 1);
+    std::fs::write(binary_path, &bytes).unwrap();
+    assert!(load_binary(binary_path).is_err());
+    std::fs::write(binary_path, b"SWLB").unwrap();
+    assert!(load_binary(binary_path).is_err());
+    std::fs::write(binary_path, "version 2").unwrap();
+    assert!(load_binary(binary_path).is_err());
+}
+
+pub fn benchmark_load() {
+    let directory = std::env::temp_dir();
+    let binary_path = directory.join("sweep_line_benchmark.swlb");
+    let binary_path = binary_path.to_str().unwrap();
+    let text_path = directory.join("sweep_line_benchmark.txt");
+    let text_path = text_path.to_str().unwrap();
+
+    for n_segments in [10_000, 100_000, 1_000_000] {
+        let problem = create_seeded_example(n_segments, 1);
+        problem.save(text_path).unwrap();
+        save_binary(&problem, binary_path).unwrap();
+
+        let start = Instant::now();
+        let text = SweepLineProblem::load(text_path).unwrap();
+        let text_time = start.elapsed().as_secs_f64();
+        let start = Instant::now();
+        let binary = load_binary(binary_path).unwrap();
+        let binary_time = start.elapsed().as_secs_f64();
+        assert!(text.segments == binary.segments);
+
+        println!(
+            "{} segments: text {:.3}s ({} bytes), binary {:.3}s ({} bytes), {:.1}x faster",
+            n_segments,
+            text_time, std::fs::metadata(text_path).unwrap().len(),
+            binary_time, std::fs::metadata(binary_path).unwrap().len(),
+            text_time / binary_time
+        );
+    }
+}
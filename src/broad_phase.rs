@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use crate::domain::{segments_intersection, Point, Segment, SweepLineProblem};
+use crate::example_generator::{create_seeded_example, create_short_segments_example};
+use crate::incremental::SpatialGrid;
+use crate::solvers::{naive_intersection_solver, same_intersections};
+
+// Leaves of the quadtree are split when they hold more segments than this
+const QUADTREE_CAPACITY: usize = 8;
+const QUADTREE_MAX_DEPTH: u32 = 20;
+
+fn bounding_box(segment: &Segment) -> (Point, Point) {
+    (
+        Point { x: f64::min(segment.ini.x, segment.end.x), y: f64::min(segment.ini.y, segment.end.y) },
+        Point { x: f64::max(segment.ini.x, segment.end.x), y: f64::max(segment.ini.y, segment.end.y) },
+    )
+}
+
+fn boxes_overlap(a: &(Point, Point), b: &(Point, Point)) -> bool {
+    a.0.x <= b.1.x && b.0.x <= a.1.x && a.0.y <= b.1.y && b.0.y <= a.1.y
+}
+
+// Lower left corner of the overlap of two boxes. Every pair is tested only in the cell or
+// leaf that holds this point, the one place both segments are sure to meet.
+fn overlap_corner(a: &(Point, Point), b: &(Point, Point)) -> Point {
+    Point { x: f64::max(a.0.x, b.0.x), y: f64::max(a.0.y, b.0.y) }
+}
+
+// Size of the cells: the median of the larger side of the bounding boxes, so a typical
+// segment covers a few cells and the few long ones do not decide it. Not smaller than the
+// side of the square each segment would get if they were spread evenly, which keeps points
+// and tiny segments from asking for a cell each.
+pub fn cell_size_for(segments: &[Segment]) -> f64 {
+    if segments.is_empty() {
+        return 1.0;
+    }
+    let mut sides: Vec<f64> = segments.iter()
+        .map(|s| f64::max((s.end.x - s.ini.x).abs(), (s.end.y - s.ini.y).abs()))
+        .collect();
+    sides.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = sides[sides.len() / 2];
+    let (min, max) = segments.iter().map(bounding_box).fold(
+        (Point { x: f64::INFINITY, y: f64::INFINITY }, Point { x: f64::NEG_INFINITY, y: f64::NEG_INFINITY }),
+        |(min, max), (a, b)| (
+            Point { x: f64::min(min.x, a.x), y: f64::min(min.y, a.y) },
+            Point { x: f64::max(max.x, b.x), y: f64::max(max.y, b.y) },
+        ),
+    );
+    let spread = ((max.x - min.x) * (max.y - min.y) / segments.len() as f64).sqrt();
+    let cell_size = f64::max(median, spread);
+    if cell_size > 0.0 { cell_size } else { 1.0 }
+}
+
+fn write_result(problem: &mut SweepLineProblem, intersections: BTreeMap<(usize, usize), Segment>) {
+    problem.result = intersections.values().cloned().collect();
+    problem.result_pairs = intersections.keys().copied().collect();
+}
+
+// Buckets the segments in a uniform grid and tests the pairs that share a cell
+pub fn grid_intersection_solver(problem: &mut SweepLineProblem) {
+    let grid = SpatialGrid::with_cell_size(&problem.segments, cell_size_for(&problem.segments));
+    let boxes: Vec<(Point, Point)> = problem.segments.iter().map(bounding_box).collect();
+    let ranges: Vec<((i64, i64), (i64, i64))> = problem.segments.iter().map(|s| grid.cell_range(s)).collect();
+    let mut intersections = BTreeMap::new();
+    for (cell, bucket) in grid.buckets() {
+        // Indices go into the buckets in increasing order
+        for (k, &i) in bucket.iter().enumerate() {
+            for &j in bucket[k + 1..].iter() {
+                if !boxes_overlap(&boxes[i], &boxes[j]) {
+                    continue;
+                }
+                let first_cell = (i64::max(ranges[i].0 .0, ranges[j].0 .0), i64::max(ranges[i].1 .0, ranges[j].1 .0));
+                if first_cell != *cell {
+                    continue;
+                }
+                problem.basic_operations += 1;
+                if let Some(intersection) = segments_intersection(&problem.segments[i], &problem.segments[j]) {
+                    intersections.insert((i, j), intersection);
+                }
+            }
+        }
+    }
+    // Segments too large for the cells are tested against every other one
+    let mut in_cells = vec![true; problem.segments.len()];
+    for &i in grid.oversized() {
+        in_cells[i] = false;
+    }
+    for &i in grid.oversized() {
+        for j in 0..problem.segments.len() {
+            // A pair of two oversized segments is tested by the first of them
+            if j == i || (!in_cells[j] && j < i) || !boxes_overlap(&boxes[i], &boxes[j]) {
+                continue;
+            }
+            problem.basic_operations += 1;
+            if let Some(intersection) = segments_intersection(&problem.segments[i], &problem.segments[j]) {
+                intersections.insert((usize::min(i, j), usize::max(i, j)), intersection);
+            }
+        }
+    }
+    write_result(problem, intersections);
+}
+
+struct QuadNode {
+    min: Point,
+    max: Point,
+    depth: u32,
+    children: Option<[usize; 4]>,
+    segments: Vec<usize>,
+}
+
+// Every segment is in all the leaves its bounding box touches. Leaves split until they hold
+// few segments or are about as small as the grid cells would be.
+struct QuadTree {
+    nodes: Vec<QuadNode>,
+    boxes: Vec<(Point, Point)>,
+    max_depth: u32,
+}
+
+impl QuadTree {
+    fn new(segments: &[Segment]) -> QuadTree {
+        let boxes: Vec<(Point, Point)> = segments.iter().map(bounding_box).collect();
+        let min = Point {
+            x: boxes.iter().map(|b| b.0.x).fold(f64::INFINITY, f64::min),
+            y: boxes.iter().map(|b| b.0.y).fold(f64::INFINITY, f64::min),
+        };
+        let max_x = boxes.iter().map(|b| b.1.x).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = boxes.iter().map(|b| b.1.y).fold(f64::NEG_INFINITY, f64::max);
+        let side = f64::max(max_x - min.x, max_y - min.y);
+        let max_depth = (side / cell_size_for(segments)).log2().ceil().clamp(0.0, QUADTREE_MAX_DEPTH as f64) as u32;
+        let root = QuadNode {
+            max: Point { x: min.x + side, y: min.y + side },
+            min,
+            depth: 0,
+            children: None,
+            segments: Vec::new(),
+        };
+        let mut tree = QuadTree { nodes: vec![root], boxes, max_depth };
+        for i in 0..segments.len() {
+            tree.insert(0, i);
+        }
+        tree
+    }
+
+    fn touches(&self, node: usize, i: usize) -> bool {
+        let node = &self.nodes[node];
+        boxes_overlap(&(node.min.clone(), node.max.clone()), &self.boxes[i])
+    }
+
+    fn insert(&mut self, node: usize, i: usize) {
+        if let Some(children) = self.nodes[node].children {
+            for child in children {
+                if self.touches(child, i) {
+                    self.insert(child, i);
+                }
+            }
+            return;
+        }
+        self.nodes[node].segments.push(i);
+        if self.nodes[node].segments.len() > QUADTREE_CAPACITY && self.nodes[node].depth < self.max_depth {
+            self.split(node);
+        }
+    }
+
+    fn split(&mut self, node: usize) {
+        let (min, max, depth) = (self.nodes[node].min.clone(), self.nodes[node].max.clone(), self.nodes[node].depth);
+        // Both halves share the middle exactly, so every point of the node is in one leaf
+        let mid = Point { x: (min.x + max.x) / 2.0, y: (min.y + max.y) / 2.0 };
+        let quadrants = [
+            (min.clone(), mid.clone()),
+            (Point { x: mid.x, y: min.y }, Point { x: max.x, y: mid.y }),
+            (Point { x: min.x, y: mid.y }, Point { x: mid.x, y: max.y }),
+            (mid.clone(), max.clone()),
+        ];
+        let mut children = [0; 4];
+        for (child, (min, max)) in children.iter_mut().zip(quadrants) {
+            *child = self.nodes.len();
+            self.nodes.push(QuadNode { min, max, depth: depth + 1, children: None, segments: Vec::new() });
+        }
+        self.nodes[node].children = Some(children);
+        for i in std::mem::take(&mut self.nodes[node].segments) {
+            self.insert(node, i);
+        }
+    }
+
+    // Leaves are closed on the lower sides and open on the upper ones, but for those on
+    // the upper sides of the whole tree
+    fn owns(&self, node: usize, p: &Point) -> bool {
+        let (node, root) = (&self.nodes[node], &self.nodes[0]);
+        p.x >= node.min.x && (p.x < node.max.x || node.max.x == root.max.x)
+            && p.y >= node.min.y && (p.y < node.max.y || node.max.y == root.max.y)
+    }
+}
+
+// Tests the pairs of segments that share a leaf of a quadtree. Unlike the grid it adapts to
+// segments crowded in some places and sparse in others.
+pub fn quadtree_intersection_solver(problem: &mut SweepLineProblem) {
+    if problem.segments.is_empty() {
+        write_result(problem, BTreeMap::new());
+        return;
+    }
+    let tree = QuadTree::new(&problem.segments);
+    let mut intersections = BTreeMap::new();
+    for (node, leaf) in tree.nodes.iter().enumerate() {
+        if leaf.children.is_some() {
+            continue;
+        }
+        for (k, &i) in leaf.segments.iter().enumerate() {
+            for &j in leaf.segments[k + 1..].iter() {
+                let (a, b) = (&tree.boxes[i], &tree.boxes[j]);
+                if !boxes_overlap(a, b) || !tree.owns(node, &overlap_corner(a, b)) {
+                    continue;
+                }
+                problem.basic_operations += 1;
+                let pair = (usize::min(i, j), usize::max(i, j));
+                if let Some(intersection) = segments_intersection(&problem.segments[pair.0], &problem.segments[pair.1]) {
+                    intersections.insert(pair, intersection);
+                }
+            }
+        }
+    }
+    write_result(problem, intersections);
+}
+
+pub fn test_broad_phase_solvers() {
+    let pairs = |problem: &SweepLineProblem| -> Vec<(Segment, (usize, usize))> {
+        problem.result.iter().cloned().zip(problem.result_pairs.iter().cloned()).collect()
+    };
+    let segment = |x0: f64, y0: f64, x1: f64, y1: f64| Segment { ini: Point { x: x0, y: y0 }, end: Point { x: x1, y: y1 } };
+    // Segments along cell and leaf edges, touching at corners, overlapping, repeated and
+    // reduced to a point
+    let mut edges = Vec::new();
+    for k in 0..12 {
+        let k = k as f64;
+        edges.push(segment(k, 0.0, k, 11.0));
+        edges.push(segment(0.0, k, 11.0, k));
+        edges.push(segment(k, k, k + 1.0, k + 1.0));
+    }
+    edges.push(segment(2.0, 2.0, 2.0, 2.0));
+    edges.push(segment(3.0, 0.0, 3.0, 11.0));
+    let mut mixed = create_short_segments_example(3000, 1000.0, 20.0, 6).segments;
+    mixed.extend(create_seeded_example(40, 6).segments);
+    // Short segments and a few that span far more cells than the grid puts a segment in
+    let mut far = create_short_segments_example(2000, 1000.0, 20.0, 7).segments;
+    far.push(segment(-1e6, 500.0, 1e6, 500.0));
+    far.push(segment(0.0, 0.0, 1000.0, 1000.0));
+    far.push(segment(500.0, -1e6, 500.0, 1e6));
+
+    let problems = [
+        SweepLineProblem::new(Vec::new()),
+        SweepLineProblem::new(vec![segment(1.0, 1.0, 2.0, 2.0)]),
+        SweepLineProblem::new(edges),
+        create_seeded_example(300, 4),
+        create_short_segments_example(3000, 1000.0, 30.0, 5),
+        SweepLineProblem::new(mixed),
+        SweepLineProblem::new(far),
+    ];
+    for problem in problems {
+        let mut reference = problem.clone();
+        naive_intersection_solver(&mut reference);
+        for (name, solver) in [("grid", grid_intersection_solver as fn(&mut SweepLineProblem)), ("quadtree", quadtree_intersection_solver)] {
+            let mut solved = problem.clone();
+            solver(&mut solved);
+            assert!(same_intersections(&pairs(&solved), &pairs(&reference)), "{} on {} segments", name, problem.segments.len());
+            assert!(solved.basic_operations <= reference.basic_operations.max(1));
+        }
+    }
+
+    // The cells follow the segments, not the extent of the plane
+    let short = create_short_segments_example(1000, 10000.0, 10.0, 1);
+    assert!(cell_size_for(&short.segments) < 500.0);
+    assert!(cell_size_for(&create_seeded_example(100, 1).segments) > 100.0);
+}
@@ -0,0 +1,133 @@
+use crate::domain::{Point, SweepLineProblem};
+
+const MIN_SCALE: f64 = 1e-6;
+const MAX_SCALE: f64 = 1e6;
+// Fraction of the window left around the data by `fit`
+const FIT_MARGIN: f64 = 0.05;
+
+// Maps world coordinates, the ones of the segments, to window pixels
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Camera {
+    // World point drawn at the top left corner of the window
+    pub origin: Point,
+    // Pixels per world unit
+    pub scale: f64,
+}
+
+impl Default for Camera {
+    // World units are pixels, as before there was a camera
+    fn default() -> Self {
+        Camera { origin: Point { x: 0.0, y: 0.0 }, scale: 1.0 }
+    }
+}
+
+impl Camera {
+    pub fn world_to_screen(&self, p: &Point) -> Point {
+        Point { x: (p.x - self.origin.x) * self.scale, y: (p.y - self.origin.y) * self.scale }
+    }
+
+    pub fn screen_to_world(&self, p: &Point) -> Point {
+        Point { x: p.x / self.scale + self.origin.x, y: p.y / self.scale + self.origin.y }
+    }
+
+    // Moves the view by the given pixels, the world follows the mouse
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.origin.x -= dx / self.scale;
+        self.origin.y -= dy / self.scale;
+    }
+
+    // Zooms by the factor keeping the world point under the screen point in place
+    pub fn zoom_at(&mut self, screen: &Point, factor: f64) {
+        let anchor = self.screen_to_world(screen);
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.origin = Point { x: anchor.x - screen.x / self.scale, y: anchor.y - screen.y / self.scale };
+    }
+
+    // Visible world region as (x min, y min, x max, y max)
+    pub fn visible(&self, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        let corner = self.screen_to_world(&Point { x: width, y: height });
+        (self.origin.x, self.origin.y, corner.x, corner.y)
+    }
+
+    // Centers the segments in the window, as large as they fit with a margin
+    pub fn fit(&mut self, problem: &SweepLineProblem, width: f64, height: f64) {
+        let points: Vec<&Point> = problem.segments.iter()
+            .flat_map(|segment| [&segment.ini, &segment.end])
+            .collect();
+        if points.is_empty() {
+            *self = Camera::default();
+            return;
+        }
+        let x_min = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+        let y_min = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+        let x_max = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+        let y_max = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+        let usable = 1.0 - 2.0 * FIT_MARGIN;
+        // A single point or a line along an axis keeps the current zoom on that axis
+        let scale_x = if x_max > x_min { width * usable / (x_max - x_min) } else { f64::INFINITY };
+        let scale_y = if y_max > y_min { height * usable / (y_max - y_min) } else { f64::INFINITY };
+        let scale = f64::min(scale_x, scale_y);
+        if scale.is_finite() {
+            self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        }
+        self.origin = Point {
+            x: (x_min + x_max) / 2.0 - width / 2.0 / self.scale,
+            y: (y_min + y_max) / 2.0 - height / 2.0 / self.scale,
+        };
+    }
+
+    // Smallest 1, 2 or 5 times a power of ten, in world units, that is at least
+    // `min_pixels` apart on the screen
+    pub fn grid_size(&self, min_pixels: f64) -> f64 {
+        let target = min_pixels / self.scale;
+        // The search below never ends without a positive and finite target
+        if !(target.is_finite() && target > 0.0) {
+            return 1.0;
+        }
+        let mut power = 10f64.powf(target.log10().floor());
+        loop {
+            for step in [1.0, 2.0, 5.0] {
+                // Relative tolerance so that a spacing equal to the target is taken
+                if step * power >= target * (1.0 - 1e-9) {
+                    return step * power;
+                }
+            }
+            power *= 10.0;
+        }
+    }
+}
+
+pub fn test_camera() {
+    let mut camera = Camera::default();
+    assert_eq!(camera.grid_size(10.0), 10.0);
+    for min_pixels in [0.0, -10.0, f64::NAN, f64::INFINITY] {
+        assert_eq!(camera.grid_size(min_pixels), 1.0);
+    }
+    let p = Point { x: 123.0, y: -45.0 };
+    assert_eq!(camera.world_to_screen(&p), p);
+
+    // The world point under the mouse stays there while zooming
+    let mouse = Point { x: 200.0, y: 100.0 };
+    let under_mouse = camera.screen_to_world(&mouse);
+    camera.zoom_at(&mouse, 4.0);
+    let moved = camera.world_to_screen(&under_mouse);
+    assert!((moved.x - mouse.x).abs() < 1e-9 && (moved.y - mouse.y).abs() < 1e-9);
+    assert_eq!(camera.grid_size(10.0), 5.0);
+    camera.zoom_at(&mouse, 1.0 / 400.0);
+    assert_eq!(camera.grid_size(10.0), 1000.0);
+
+    camera.pan(10.0, -20.0);
+    let back = camera.screen_to_world(&camera.world_to_screen(&p));
+    assert!((back.x - p.x).abs() < 1e-9 && (back.y - p.y).abs() < 1e-9);
+
+    let problem = SweepLineProblem::load("problems/sweep_line_problem_0.txt").unwrap();
+    camera.fit(&problem, 800.0, 600.0);
+    let (x_min, y_min, x_max, y_max) = camera.visible(800.0, 600.0);
+    for segment in problem.segments.iter() {
+        for q in [&segment.ini, &segment.end] {
+            assert!(x_min < q.x && q.x < x_max && y_min < q.y && q.y < y_max);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::{BufRead, Write};
+use std::io::BufRead;
 #[derive(Clone, PartialOrd)]
 #[derive(PartialEq)]
 #[derive(Debug)]
@@ -100,46 +100,354 @@ pub enum Direction {
     CounterClockwise,
 }
 
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+#[derive(Debug, Default)]
+pub enum SegmentColor {
+    #[default]
+    Red,
+    Blue,
+}
+
+impl SegmentColor {
+    pub fn parse(value: &str) -> Option<SegmentColor> {
+        match value {
+            "red" | "r" => Some(SegmentColor::Red),
+            "blue" | "b" => Some(SegmentColor::Blue),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SegmentColor::Red => "red",
+            SegmentColor::Blue => "blue",
+        }
+    }
+}
+
+// Per segment data that is not part of the geometry, kept parallel to `segments`. Most
+// segments have no label or tags, so those are only allocated for the ones that do and a
+// segment without them costs 16 bytes.
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug, Default)]
+pub struct SegmentInfo {
+    pub color: SegmentColor,
+    // None when the label and the tags are both empty
+    names: Option<Box<SegmentNames>>,
+}
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug, Default)]
+struct SegmentNames {
+    label: String,
+    tags: Vec<String>,
+}
+
+impl SegmentInfo {
+    // A segment with a color and no label or tags
+    pub fn colored(color: SegmentColor) -> SegmentInfo {
+        SegmentInfo { color, names: None }
+    }
+
+    pub fn new(color: SegmentColor, label: String, tags: Vec<String>) -> SegmentInfo {
+        let mut info = SegmentInfo::colored(color);
+        info.set_names(label, tags);
+        info
+    }
+
+    pub fn label(&self) -> &str {
+        self.names.as_ref().map_or("", |names| names.label.as_str())
+    }
+
+    pub fn tags(&self) -> &[String] {
+        self.names.as_ref().map_or(&[], |names| names.tags.as_slice())
+    }
+
+    pub fn set_label(&mut self, label: String) {
+        let tags = self.names.take().map(|names| names.tags).unwrap_or_default();
+        self.set_names(label, tags);
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        let label = self.names.take().map(|names| names.label).unwrap_or_default();
+        self.set_names(label, tags);
+    }
+
+    fn set_names(&mut self, label: String, tags: Vec<String>) {
+        self.names = if label.is_empty() && tags.is_empty() {
+            None
+        } else {
+            Some(Box::new(SegmentNames { label, tags }))
+        };
+    }
+}
+
 #[derive(Clone)]
 pub struct SweepLineProblem {
     pub segments: Vec<Segment>,
+    pub segment_info: Vec<SegmentInfo>,
+    // Header entries of the file (name, seed, generator...) in the order they appear
+    pub metadata: Vec<(String, String)>,
+    // Intersections the file says the solvers must find, with their segment pairs
+    pub expected: Option<Vec<(Segment, (usize, usize))>>,
+    // Name of the solver, one of `SOLVERS`, that found `expected` and is checked against it
+    pub expected_solver: String,
     pub result: Vec<Segment>,
+    // Indices of the two input segments behind each entry of `result`
+    pub result_pairs: Vec<(usize, usize)>,
     pub time: f64,
     pub basic_operations: i32
 }
 
+pub const PROBLEM_FILE_VERSION: i32 = 2;
+pub const DEFAULT_EXPECTED_SOLVER: &str = "sweep line";
+
+// Splits a line of a problem file into tokens. Whitespace separates tokens unless it is
+// inside double quotes, and everything after a `#` outside quotes is a comment.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            '\\' if quoted => {
+                if let Some(escaped) = chars.next() {
+                    token.push(escaped);
+                }
+            }
+            '#' if !quoted => break,
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            c => {
+                token.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(token);
+    }
+    tokens
+}
+
+// Quotes the value if it would not survive `tokenize` as a single token
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '#' || c == '\\') {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn parse_coordinates(tokens: &[String], line_number: usize) -> Result<Segment, String> {
+    if tokens.len() < 4 {
+        return Err(format!("line {}: expected four coordinates", line_number));
+    }
+    let mut values = [0.0; 4];
+    for (value, token) in values.iter_mut().zip(tokens.iter()) {
+        *value = token.parse::<f64>()
+            .map_err(|_| format!("line {}: '{}' is not a number", line_number, token))?;
+    }
+    Ok(Segment{ini: Point{x: values[0], y: values[1]}, end: Point{x: values[2], y: values[3]}})
+}
+
 impl SweepLineProblem {
 
-    pub fn load(path: &str) -> SweepLineProblem {
-        let content = std::fs::read_to_string(path).unwrap();
-        let mut lines = content.lines();
-        let n_segments = lines.next().unwrap().parse::<i32>().unwrap();
-        let mut segments = Vec::new();
+    pub fn new(segments: Vec<Segment>) -> SweepLineProblem {
+        SweepLineProblem {
+            segment_info: vec![SegmentInfo::default(); segments.len()],
+            segments,
+            metadata: Vec::new(),
+            expected: None,
+            expected_solver: DEFAULT_EXPECTED_SOLVER.to_string(),
+            result: Vec::new(),
+            result_pairs: Vec::new(),
+            time: 0.0,
+            basic_operations: 0,
+        }
+    }
+
+    // Reads both the versioned format written by `save` and the original one, a count
+    // followed by one segment per line. Errors name the line that could not be read.
+    pub fn load(path: &str) -> Result<SweepLineProblem, String> {
+        let content = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        SweepLineProblem::parse(&content).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    pub fn parse(content: &str) -> Result<SweepLineProblem, String> {
+        let mut lines = content.lines().enumerate()
+            .map(|(i, line)| (i + 1, tokenize(line)))
+            .filter(|(_, tokens)| !tokens.is_empty());
+        let Some((line_number, first)) = lines.next() else { return Err("empty problem file".to_string()) };
+        if first[0] != "version" {
+            return SweepLineProblem::parse_unversioned(content);
+        }
+        let version = first.get(1).and_then(|value| value.parse::<i32>().ok());
+        if version != Some(PROBLEM_FILE_VERSION) {
+            return Err(format!("line {}: unsupported problem file version {:?}", line_number, first.get(1)));
+        }
+
+        let mut problem = SweepLineProblem::new(Vec::new());
+        let mut n_segments = None;
+        for (line_number, tokens) in lines.by_ref() {
+            if tokens[0] == "segments" {
+                n_segments = tokens.get(1).and_then(|value| value.parse::<usize>().ok());
+                if n_segments.is_none() {
+                    return Err(format!("line {}: expected the number of segments", line_number));
+                }
+                break;
+            }
+            problem.metadata.push((tokens[0].clone(), tokens[1..].join(" ")));
+        }
+        for _ in 0..n_segments.unwrap_or(0) {
+            let Some((line_number, tokens)) = lines.next() else {
+                return Err("fewer segments than announced".to_string())
+            };
+            let segment = parse_coordinates(&tokens, line_number)?;
+            let mut info = SegmentInfo::default();
+            for attribute in tokens[4..].iter() {
+                let Some((key, value)) = attribute.split_once('=') else {
+                    return Err(format!("line {}: expected key=value, found '{}'", line_number, attribute))
+                };
+                match key {
+                    "color" => info.color = SegmentColor::parse(value)
+                        .ok_or_else(|| format!("line {}: unknown color '{}'", line_number, value))?,
+                    "label" => info.set_label(value.to_string()),
+                    "tags" => info.set_tags(value.split(',')
+                        .filter(|tag| !tag.is_empty())
+                        .map(|tag| tag.to_string())
+                        .collect()),
+                    // Attributes from newer writers are skipped
+                    _ => (),
+                }
+            }
+            problem.add_segment(segment, info);
+        }
+
+        if let Some((line_number, tokens)) = lines.next() {
+            let n_expected = match (tokens[0].as_str(), tokens.get(1)) {
+                ("expected", Some(value)) => value.parse::<usize>().ok(),
+                _ => None,
+            };
+            let Some(n_expected) = n_expected else {
+                return Err(format!("line {}: expected the number of expected intersections", line_number))
+            };
+            // Files from before the solver was written down were checked with the sweep
+            problem.expected_solver = tokens.get(2)
+                .and_then(|value| value.strip_prefix("solver="))
+                .unwrap_or(DEFAULT_EXPECTED_SOLVER)
+                .to_string();
+            let mut expected = Vec::new();
+            for _ in 0..n_expected {
+                let Some((line_number, tokens)) = lines.next() else {
+                    return Err("fewer expected intersections than announced".to_string())
+                };
+                let intersection = parse_coordinates(&tokens, line_number)?;
+                let pair = tokens.get(4)
+                    .and_then(|value| value.strip_prefix("segments="))
+                    .and_then(|value| value.split_once(','))
+                    .and_then(|(i, j)| Some((i.parse::<usize>().ok()?, j.parse::<usize>().ok()?)));
+                let Some(pair) = pair else {
+                    return Err(format!("line {}: expected segments=i,j", line_number))
+                };
+                expected.push((intersection, pair));
+            }
+            problem.expected = Some(expected);
+        }
+        Ok(problem)
+    }
+
+    fn parse_unversioned(content: &str) -> Result<SweepLineProblem, String> {
+        let mut lines = content.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let n_segments = lines.next()
+            .and_then(|(_, line)| line.trim().parse::<usize>().ok())
+            .ok_or_else(|| "line 1: expected the number of segments".to_string())?;
+        let mut problem = SweepLineProblem::new(Vec::new());
         for _ in 0..n_segments {
-            let line = lines.next().unwrap();
-            let mut values = line.split_whitespace();
-            let x1 = values.next().unwrap().parse::<f64>().unwrap();
-            let y1 = values.next().unwrap().parse::<f64>().unwrap();
-            let x2 = values.next().unwrap().parse::<f64>().unwrap();
-            let y2 = values.next().unwrap().parse::<f64>().unwrap();
-            segments.push(Segment{ini: Point{x: x1, y: y1}, end: Point{x: x2, y: y2}});
+            let Some((line_number, line)) = lines.next() else {
+                return Err("fewer segments than announced".to_string())
+            };
+            let tokens: Vec<String> = line.split_whitespace().map(|token| token.to_string()).collect();
+            let segment = parse_coordinates(&tokens, line_number)?;
+            // The color column is optional, older files only have the coordinates
+            let color = match tokens.get(4) {
+                Some(value) => SegmentColor::parse(value)
+                    .ok_or_else(|| format!("line {}: unknown color '{}'", line_number, value))?,
+                None => SegmentColor::default(),
+            };
+            problem.add_segment(segment, SegmentInfo::colored(color));
         }
-        return SweepLineProblem{segments, result: Vec::new(), time: 0.0, basic_operations: 0};
+        Ok(problem)
     }
 
-    pub fn save(&self, path: &str) {
-        let mut file = std::fs::File::create(path).unwrap();
+    pub fn save(&self, path: &str) -> Result<(), String> {
         let mut content = String::new();
-        content.push_str(&format!("{}\n", self.segments.len()));
-        for segment in self.segments.iter() {
+        content.push_str(&format!("version {}\n", PROBLEM_FILE_VERSION));
+        for (key, value) in self.metadata.iter() {
+            content.push_str(&format!("{} {}\n", key, quote(value)));
+        }
+        content.push_str(&format!("segments {}\n", self.segments.len()));
+        for (segment, info) in self.segments.iter().zip(self.segment_info.iter()) {
             content.push_str(
                 &format!(
-                    "{} {} {} {}\n",
-                    segment.ini.x, segment.ini.y, segment.end.x, segment.end.y
+                    "{} {} {} {} color={}",
+                    segment.ini.x, segment.ini.y, segment.end.x, segment.end.y,
+                    info.color.name()
                 )
             );
+            if !info.label().is_empty() {
+                content.push_str(&format!(" {}", quote(&format!("label={}", info.label()))));
+            }
+            if !info.tags().is_empty() {
+                content.push_str(&format!(" {}", quote(&format!("tags={}", info.tags().join(",")))));
+            }
+            content.push('\n');
         }
-        file.write_all(content.as_bytes()).unwrap();
+        if let Some(expected) = &self.expected {
+            content.push_str(&format!(
+                "expected {} {}\n", expected.len(), quote(&format!("solver={}", self.expected_solver))
+            ));
+            for (intersection, (i, j)) in expected.iter() {
+                content.push_str(
+                    &format!(
+                        "{} {} {} {} segments={},{}\n",
+                        intersection.ini.x, intersection.ini.y,
+                        intersection.end.x, intersection.end.y, i, j
+                    )
+                );
+            }
+        }
+        std::fs::write(path, content).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    // Stores the current result as the expected one, so that it is saved with the problem.
+    // `solver` is the name of the solver that found it.
+    pub fn record_expected(&mut self, solver: &str) {
+        self.expected_solver = solver.to_string();
+        self.expected = Some(
+            self.result.iter().cloned().zip(self.result_pairs.iter().cloned()).collect()
+        );
+    }
+
+    pub fn add_segment(&mut self, segment: Segment, info: SegmentInfo) {
+        self.segments.push(segment);
+        self.segment_info.push(info);
+    }
+
+    pub fn remove_segment(&mut self, i: usize) -> (Segment, SegmentInfo) {
+        (self.segments.remove(i), self.segment_info.remove(i))
     }
 
 }
@@ -210,23 +518,126 @@ pub fn segments_intersection(s1: &Segment, s2: &Segment) -> Option<Segment> {
 #[derive(Clone)]
 #[derive(PartialEq)]
 #[derive(Debug)]
-pub struct Node{
-    pub key: Segment,
+pub struct Node<K = Segment> {
+    pub key: K,
     pub priority: i32,
-    pub left: Option<Box<Node>>,
-    pub right: Option<Box<Node>>,
+    pub left: Option<Box<Node<K>>>,
+    pub right: Option<Box<Node<K>>>,
 }
 
+// Keys are ordered by `Ord` when they are inserted one by one. A treap built with
+// `split_while` and `push` keeps the keys in the order they were given, so it can hold keys
+// whose order depends on more than the keys, like segments ordered at the sweep line.
 #[derive(Clone)]
-pub struct Treap {
-    root: Option<Box<Node>>,
+pub struct Treap<K = Segment> {
+    root: Option<Box<Node<K>>>,
 }
 
-impl Treap {
-    pub fn new() -> Treap {
+impl<K> Treap<K> {
+    pub fn new() -> Treap<K> {
         Treap { root: None }
     }
 
+    fn split_node_while(
+        node: Option<Box<Node<K>>>, below: &impl Fn(&K) -> bool, basic_operations: &mut i32
+    ) -> (Option<Box<Node<K>>>, Option<Box<Node<K>>>) {
+        *basic_operations += 1;
+        match node {
+            None => (None, None),
+            Some(mut node) => {
+                if below(&node.key) {
+                    let (left, right) =
+                        Self::split_node_while(node.right.take(), below, basic_operations);
+                    node.right = left;
+                    (Some(node), right)
+                } else {
+                    let (left, right) =
+                        Self::split_node_while(node.left.take(), below, basic_operations);
+                    node.left = right;
+                    (left, Some(node))
+                }
+            }
+        }
+    }
+
+    // Takes out the first keys, the ones for which `below` is true. They have to come before
+    // all the others.
+    pub fn split_while(&mut self, below: impl Fn(&K) -> bool, basic_operations: &mut i32) -> Treap<K> {
+        let (left, right) = Self::split_node_while(self.root.take(), &below, basic_operations);
+        self.root = right;
+        Treap { root: left }
+    }
+
+    // Puts the keys of `other` after the ones of this treap
+    pub fn append(&mut self, other: Treap<K>, basic_operations: &mut i32) {
+        let root = self.root.take();
+        self.root = self.merge(root, other.root, basic_operations);
+    }
+
+    // Puts the key after all the others
+    pub fn push(&mut self, key: K, basic_operations: &mut i32) {
+        let node = Node {
+            key,
+            priority: rand::random::<i32>(),
+            left: None,
+            right: None,
+        };
+        self.append(Treap { root: Some(Box::new(node)) }, basic_operations);
+    }
+
+    pub fn first(&self) -> Option<&K> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = node.left.as_ref() {
+            node = left;
+        }
+        Some(&node.key)
+    }
+
+    pub fn last(&self) -> Option<&K> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = node.right.as_ref() {
+            node = right;
+        }
+        Some(&node.key)
+    }
+
+    pub fn merge(
+        &self, left: Option<Box<Node<K>>>, right: Option<Box<Node<K>>>, basic_operations: &mut i32
+    ) -> Option<Box<Node<K>>> {
+        *basic_operations += 1;
+        match (left, right) {
+            (None, None) => None,
+            (Some(left), None) => Some(left),
+            (None, Some(right)) => Some(right),
+            (Some(mut left), Some(mut right)) => {
+                if left.priority > right.priority {
+                    left.right = self.merge(left.right, Some(right), basic_operations);
+                    Some(left)
+                } else {
+                    right.left = self.merge(Some(left), right.left, basic_operations);
+                    Some(right)
+                }
+            }
+        }
+    }
+
+    fn collect_inorder(&self, node: &Option<Box<Node<K>>>, keys: &mut Vec<K>) where K: Clone {
+        if let Some(node) = node {
+            self.collect_inorder(&node.left, keys);
+            keys.push(node.key.clone());
+            self.collect_inorder(&node.right, keys);
+        }
+    }
+
+    // Keys in order, from the smallest to the largest
+    pub fn to_vec(&self) -> Vec<K> where K: Clone {
+        let mut keys = Vec::new();
+        self.collect_inorder(&self.root, &mut keys);
+        keys
+    }
+}
+
+impl Treap {
     fn split(
         &self, node: Option<Box<Node>>, key: &Segment, basic_operations: &mut i32
     ) -> (Option<Box<Node>>, Option<Box<Node>>) {
@@ -268,26 +679,6 @@ impl Treap {
         );
     }
 
-    pub fn merge(
-        &self, left: Option<Box<Node>>, right: Option<Box<Node>>, basic_operations: &mut i32
-    ) -> Option<Box<Node>> {
-        *basic_operations += 1;
-        match (left, right) {
-            (None, None) => None,
-            (Some(left), None) => Some(left),
-            (None, Some(right)) => Some(right),
-            (Some(mut left), Some(mut right)) => {
-                if left.priority > right.priority {
-                    left.right = self.merge(left.right, Some(right), basic_operations);
-                    Some(left)
-                } else {
-                    right.left = self.merge(Some(left), right.left, basic_operations);
-                    Some(right)
-                }
-            }
-        }
-    }
-
     fn find_node(
         &self, node: Option<Box<Node>>, key: &Segment, basic_operations: &mut i32
     ) -> Option<Box<Node>> {
@@ -394,6 +785,7 @@ pub fn test_treap() {
             segments.push(segment.clone());
         }
         segments.sort_by(|a, b| a.cmp(b));
+        assert_eq!(treap.to_vec(), segments);
         // println!("---------------------------------------");
         println!("Segments inserted in the treap:");
         treap.print_inorder();
@@ -425,8 +817,97 @@ pub fn test_treap() {
         }
         assert_eq!(treap.root, None);
     }
+
+    // Keys in the order they are pushed, split at any position and put back together
+    for _ in 0..1000 {
+        let ref mut basic_operations = 0;
+        let keys: Vec<usize> = (0..n).collect();
+        let mut treap = Treap::new();
+        for &key in keys.iter() {
+            treap.push(key, basic_operations);
+        }
+        let at = rand::random::<usize>() % (n + 1);
+        let mut below = treap.split_while(|&key| key < at, basic_operations);
+        assert_eq!(below.to_vec(), keys[..at].to_vec());
+        assert_eq!(treap.to_vec(), keys[at..].to_vec());
+        assert_eq!(below.last(), keys[..at].last());
+        assert_eq!(treap.first(), keys[at..].first());
+        below.append(treap, basic_operations);
+        assert_eq!(below.to_vec(), keys);
+    }
 }
 
 
 
 
+
+pub fn test_problem_file() {
+    let path = std::env::temp_dir().join("sweep_line_problem_format.txt");
+    let path = path.to_str().unwrap();
+
+    let mut problem = SweepLineProblem::new(Vec::new());
+    problem.metadata.push(("name".to_string(), "two \"roads\" # and a pipe".to_string()));
+    problem.metadata.push(("seed".to_string(), "42".to_string()));
+    problem.add_segment(
+        Segment{ini: Point{x: 0.0, y: 0.0}, end: Point{x: 10.0, y: 10.0}},
+        SegmentInfo::new(SegmentColor::Red, "Main street".to_string(), vec!["road".to_string()])
+    );
+    problem.add_segment(
+        Segment{ini: Point{x: 0.1, y: 10.0}, end: Point{x: 10.0, y: 1.0 / 3.0}},
+        SegmentInfo::new(SegmentColor::Blue, String::new(), vec!["pipe".to_string(), "water".to_string()])
+    );
+    problem.result.push(Segment{ini: Point{x: 5.0, y: 5.0}, end: Point{x: 5.0, y: 5.0}});
+    problem.result_pairs.push((0, 1));
+    problem.record_expected("naive");
+    problem.save(path).unwrap();
+
+    let loaded = SweepLineProblem::load(path).unwrap();
+    assert_eq!(loaded.segments, problem.segments);
+    assert_eq!(loaded.segment_info, problem.segment_info);
+    assert_eq!(loaded.segment_info[1].tags(), ["pipe".to_string(), "water".to_string()]);
+    assert_eq!(loaded.metadata, problem.metadata);
+
+    // Segments without a label or tags stay small, and clearing them frees the names
+    assert!(std::mem::size_of::<SegmentInfo>() <= 16);
+    let mut info = loaded.segment_info[0].clone();
+    info.set_label(String::new());
+    info.set_tags(Vec::new());
+    assert_eq!(info, SegmentInfo::colored(SegmentColor::Red));
+    assert_eq!(loaded.expected, problem.expected);
+    assert_eq!(loaded.expected_solver, "naive");
+
+    // Comments and blank lines are allowed anywhere, the header is optional
+    std::fs::write(path, "# hand written\nversion 2\n\nsegments 1 # one\n1 2 3 4 # a segment\n").unwrap();
+    let loaded = SweepLineProblem::load(path).unwrap();
+    assert_eq!(loaded.segments, vec![Segment{ini: Point{x: 1.0, y: 2.0}, end: Point{x: 3.0, y: 4.0}}]);
+    assert_eq!(loaded.expected, None);
+
+    // Malformed files are reported with the line that could not be read
+    for (content, error) in [
+        ("", "empty problem file"),
+        ("version 3\nsegments 0\n", "line 1: unsupported problem file version"),
+        ("version 2\nsegments many\n", "line 2: expected the number of segments"),
+        ("version 2\nsegments 2\n1 2 3 4\n", "fewer segments than announced"),
+        ("version 2\n\nsegments 1\n1 2 3\n", "line 4: expected four coordinates"),
+        ("version 2\nsegments 1\n1 2 x 4\n", "line 3: 'x' is not a number"),
+        ("version 2\nsegments 1\n1 2 3 4 color\n", "line 3: expected key=value"),
+        ("version 2\nsegments 1\n1 2 3 4 color=green\n", "line 3: unknown color 'green'"),
+        ("version 2\nsegments 1\n1 2 3 4\nfound 1\n", "line 4: expected the number of expected intersections"),
+        ("version 2\nsegments 1\n1 2 3 4\nexpected 1\n1 2 1 2\n", "line 5: expected segments=i,j"),
+        ("two\n", "line 1: expected the number of segments"),
+        ("2\n1 2 3 4\n", "fewer segments than announced"),
+        ("1\n1 2 3 4 green\n", "line 2: unknown color 'green'"),
+        ("1\n1 2 3\n", "line 2: expected four coordinates"),
+    ] {
+        let result = SweepLineProblem::parse(content);
+        assert!(result.as_ref().is_err_and(|message| message.starts_with(error)), "{:?}: {:?}", content, result.err());
+    }
+    std::fs::write(path, "version 2\nsegments 1\n1 2 3 4 color=green\n").unwrap();
+    assert_eq!(SweepLineProblem::load(path).err(), Some(format!("{}: line 3: unknown color 'green'", path)));
+
+    // Files from before the versioned format still load
+    for i in 0..16 {
+        let problem = SweepLineProblem::load(&format!("problems/sweep_line_problem_{}.txt", i)).unwrap();
+        assert_eq!(problem.segments.len(), problem.segment_info.len());
+    }
+}
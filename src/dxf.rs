@@ -0,0 +1,237 @@
+use std::fmt::Write;
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem};
+use crate::solvers::naive_intersection_solver;
+
+// Layer of the entities written for the intersections
+pub const INTERSECTION_LAYER: &str = "INTERSECTIONS";
+
+// Group code and value of a DXF pair, with the line number of the code
+struct Group {
+    code: i32,
+    value: String,
+    line: usize,
+}
+
+struct Entity {
+    kind: String,
+    groups: Vec<Group>,
+}
+
+impl Entity {
+    fn value(&self, code: i32) -> Option<&str> {
+        self.groups.iter().find(|group| group.code == code).map(|group| group.value.as_str())
+    }
+
+    fn number(&self, group: &Group) -> Result<f64, String> {
+        group.value.parse::<f64>().map_err(|_| {
+            format!("line {}: {} group {} is not a number: '{}'", group.line + 1, self.kind, group.code, group.value)
+        })
+    }
+
+    fn flags(&self) -> i32 {
+        self.value(70).and_then(|value| value.parse::<i32>().ok()).unwrap_or(0)
+    }
+
+    // Points given by the repeated 10/20 groups, in order
+    fn points(&self) -> Result<Vec<Point>, String> {
+        self.coordinate_pairs(10, 20)
+    }
+
+    fn coordinate_pairs(&self, x_code: i32, y_code: i32) -> Result<Vec<Point>, String> {
+        let mut points = Vec::new();
+        let mut x = None;
+        for group in self.groups.iter() {
+            if group.code == x_code {
+                x = Some(self.number(group)?);
+            } else if group.code == y_code {
+                let Some(x) = x.take() else {
+                    return Err(format!("line {}: {} y coordinate without x", group.line + 1, self.kind));
+                };
+                points.push(Point { x, y: self.number(group)? });
+            }
+        }
+        Ok(points)
+    }
+}
+
+fn read_groups(text: &str) -> Result<Vec<Group>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut groups = Vec::new();
+    for (i, pair) in lines.chunks(2).enumerate() {
+        let line = 2 * i;
+        let code = pair[0].trim();
+        if code.is_empty() && pair.len() == 1 {
+            break;
+        }
+        let code = code.parse::<i32>()
+            .map_err(|_| format!("line {}: expected a group code, found '{}'", line + 1, code))?;
+        let Some(value) = pair.get(1) else {
+            return Err(format!("line {}: group {} without a value", line + 1, code));
+        };
+        groups.push(Group { code, value: value.trim().to_string(), line });
+    }
+    Ok(groups)
+}
+
+// Entities of the ENTITIES section, each one with the groups up to the next entity
+fn read_entities(groups: Vec<Group>) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    let mut in_entities = false;
+    let mut groups = groups.into_iter().peekable();
+    while let Some(group) = groups.next() {
+        if group.code != 0 {
+            continue;
+        }
+        match group.value.as_str() {
+            "SECTION" => {
+                in_entities = groups.peek().map_or(false, |name| name.code == 2 && name.value == "ENTITIES");
+            }
+            "ENDSEC" => in_entities = false,
+            kind if in_entities => {
+                let mut entity = Entity { kind: kind.to_string(), groups: Vec::new() };
+                while groups.peek().map_or(false, |next| next.code != 0) {
+                    entity.groups.push(groups.next().unwrap());
+                }
+                entities.push(entity);
+            }
+            _ => (),
+        }
+    }
+    entities
+}
+
+// Reads the LINE, LWPOLYLINE and POLYLINE entities of an ASCII DXF file. Every segment is
+// labelled with the handle of its entity, or the entity index without a handle, and tagged
+// with its layer. Polyline bulges are ignored, arcs become straight segments.
+pub fn parse_dxf(text: &str) -> Result<SweepLineProblem, String> {
+    let entities = read_entities(read_groups(text)?);
+    let mut problem = SweepLineProblem::new(Vec::new());
+    let mut i = 0;
+    while i < entities.len() {
+        let entity = &entities[i];
+        let label = entity.value(5).map(|handle| handle.to_string()).unwrap_or(i.to_string());
+        let layer = entity.value(8).unwrap_or("0").to_string();
+        let mut lines = Vec::new();
+        match entity.kind.as_str() {
+            "LINE" => {
+                let start = entity.coordinate_pairs(10, 20)?;
+                let end = entity.coordinate_pairs(11, 21)?;
+                match (start.first(), end.first()) {
+                    (Some(start), Some(end)) => lines.push(vec![start.clone(), end.clone()]),
+                    _ => return Err(format!("LINE {} without both endpoints", label)),
+                }
+            }
+            "LWPOLYLINE" => {
+                let mut points = entity.points()?;
+                if entity.flags() & 1 != 0 && points.len() > 2 {
+                    points.push(points[0].clone());
+                }
+                lines.push(points);
+            }
+            "POLYLINE" => {
+                // The vertices follow as VERTEX entities, up to a SEQEND
+                let mut points = Vec::new();
+                while i + 1 < entities.len() && entities[i + 1].kind == "VERTEX" {
+                    i += 1;
+                    points.extend(entities[i].points()?);
+                }
+                if i + 1 < entities.len() && entities[i + 1].kind == "SEQEND" {
+                    i += 1;
+                }
+                if entity.flags() & 1 != 0 && points.len() > 2 {
+                    points.push(points[0].clone());
+                }
+                lines.push(points);
+            }
+            _ => (),
+        }
+        for line in lines {
+            for pair in line.windows(2) {
+                problem.add_segment(
+                    Segment { ini: pair[0].clone(), end: pair[1].clone() },
+                    SegmentInfo::new(SegmentColor::Red, label.clone(), vec![layer.clone()])
+                );
+            }
+        }
+        i += 1;
+    }
+    Ok(problem)
+}
+
+pub fn load_dxf(path: &str) -> Result<SweepLineProblem, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse_dxf(&text).map_err(|error| format!("{}: {}", path, error))
+}
+
+fn write_group(dxf: &mut String, code: i32, value: impl std::fmt::Display) {
+    writeln!(dxf, "{}\n{}", code, value).unwrap();
+}
+
+// The segments as LINE entities on the layer of their first tag, and the current result on
+// the intersection layer: POINT entities for crossings, LINE entities for overlaps
+pub fn to_dxf(problem: &SweepLineProblem) -> String {
+    let mut dxf = String::new();
+    write_group(&mut dxf, 0, "SECTION");
+    write_group(&mut dxf, 2, "ENTITIES");
+    let write_line = |dxf: &mut String, segment: &Segment, layer: &str| {
+        write_group(dxf, 0, "LINE");
+        write_group(dxf, 8, layer);
+        write_group(dxf, 10, segment.ini.x);
+        write_group(dxf, 20, segment.ini.y);
+        write_group(dxf, 11, segment.end.x);
+        write_group(dxf, 21, segment.end.y);
+    };
+    for (segment, info) in problem.segments.iter().zip(problem.segment_info.iter()) {
+        write_line(&mut dxf, segment, info.tags().first().map_or("0", |tag| tag.as_str()));
+    }
+    for intersection in problem.result.iter() {
+        if intersection.ini != intersection.end {
+            write_line(&mut dxf, intersection, INTERSECTION_LAYER);
+            continue;
+        }
+        write_group(&mut dxf, 0, "POINT");
+        write_group(&mut dxf, 8, INTERSECTION_LAYER);
+        write_group(&mut dxf, 10, intersection.ini.x);
+        write_group(&mut dxf, 20, intersection.ini.y);
+    }
+    write_group(&mut dxf, 0, "ENDSEC");
+    write_group(&mut dxf, 0, "EOF");
+    dxf
+}
+
+pub fn save_dxf(problem: &SweepLineProblem, path: &str) -> Result<(), String> {
+    std::fs::write(path, to_dxf(problem)).map_err(|error| format!("{}: {}", path, error))
+}
+
+pub fn test_dxf() {
+    let text = "  0\nSECTION\n  2\nHEADER\n  9\n$ACADVER\n  1\nAC1009\n  0\nENDSEC\n\
+                0\nSECTION\n2\nENTITIES\n\
+                0\nLINE\n5\n1A\n8\nWALLS\n10\n0.0\n20\n5.0\n30\n0.0\n11\n20.0\n21\n5.0\n31\n0.0\n\
+                0\nLWPOLYLINE\n8\nDOORS\n90\n3\n70\n1\n10\n0\n20\n0\n10\n10\n20\n10\n10\n20\n20\n0\n\
+                0\nPOLYLINE\n8\nPIPES\n66\n1\n70\n0\n\
+                0\nVERTEX\n8\nPIPES\n10\n30\n20\n0\n\
+                0\nVERTEX\n8\nPIPES\n10\n40\n20\n10\n\
+                0\nSEQEND\n\
+                0\nCIRCLE\n8\nWALLS\n10\n0\n20\n0\n40\n1\n\
+                0\nENDSEC\n0\nEOF\n";
+    let mut problem = parse_dxf(text).unwrap();
+    // One line, the closed triangle and the single segment of the polyline
+    assert_eq!(problem.segments.len(), 1 + 3 + 1);
+    assert_eq!(problem.segment_info[0].label(), "1A");
+    assert_eq!(problem.segment_info[0].tags(), ["WALLS".to_string()]);
+    assert_eq!(problem.segment_info[3].tags(), ["DOORS".to_string()]);
+    assert_eq!(problem.segments[3].end, Point { x: 0.0, y: 0.0 });
+    assert_eq!(problem.segments[4], Segment { ini: Point { x: 30.0, y: 0.0 }, end: Point { x: 40.0, y: 10.0 } });
+
+    naive_intersection_solver(&mut problem);
+    let points = problem.result.iter().filter(|intersection| intersection.ini == intersection.end).count();
+    let exported = to_dxf(&problem);
+    assert_eq!(exported.matches("POINT\n8\nINTERSECTIONS\n").count(), points);
+    assert!(exported.contains("10\n5\n20\n5\n"));
+    let reloaded = parse_dxf(&exported).unwrap();
+    assert!(reloaded.segments[..problem.segments.len()] == problem.segments[..]);
+    assert_eq!(reloaded.segment_info[1].tags(), ["DOORS".to_string()]);
+
+    assert!(parse_dxf("0\nSECTION\n2\nENTITIES\n0\nLINE\n10\nx\n20\n0\n").is_err());
+    assert!(parse_dxf("LINE\n0\n").is_err());
+}
@@ -1,28 +1,59 @@
-use crate::domain::{Point, Segment, Direction, SweepLineProblem};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, Direction, SweepLineProblem, DEFAULT_EXPECTED_SOLVER};
 
 pub fn create_random_example(n_segments: i32) -> SweepLineProblem {
+    create_seeded_example(n_segments, rand::random::<u64>())
+}
+
+// Same seed, same problem. The seed is kept in the metadata so saved files can be rebuilt.
+pub fn create_seeded_example(n_segments: i32, seed: u64) -> SweepLineProblem {
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut segments = Vec::new();
+    let mut segment_info = Vec::new();
     let mut result = Vec::new();
+    let mut result_pairs = Vec::new();
     let mut time = 0.0;
     let mut basic_operations = 0;
 
     for _ in 0..n_segments {
         while true {
             let ini = Point {
-                x: (rand::random::<i32>() % 1000).abs() as f64,
-                y: (rand::random::<i32>() % 1000).abs() as f64,
+                x: (rng.gen::<i32>() % 1000).abs() as f64,
+                y: (rng.gen::<i32>() % 1000).abs() as f64,
             };
             let end = Point {
-                x: (rand::random::<i32>() % 1000).abs() as f64,
-                y: (rand::random::<i32>() % 1000).abs() as f64,
+                x: (rng.gen::<i32>() % 1000).abs() as f64,
+                y: (rng.gen::<i32>() % 1000).abs() as f64,
             };
             let segment = Segment{ini, end};
             if segment.ini.x != segment.end.x && segment.ini.y != segment.end.y {
                 segments.push(segment);
+                let color = if rng.gen::<bool>() { SegmentColor::Red } else { SegmentColor::Blue };
+                segment_info.push(SegmentInfo::colored(color));
                 break;
             }
         }
     }
+    let metadata = vec![
+        ("generator".to_string(), "create_random_example".to_string()),
+        ("seed".to_string(), seed.to_string()),
+    ];
+
+    SweepLineProblem{
+        segments, segment_info, metadata, expected: None,
+        expected_solver: DEFAULT_EXPECTED_SOLVER.to_string(), result, result_pairs, time,
+        basic_operations
+    }
+}
 
-    SweepLineProblem{segments, result, time, basic_operations}
+// Short segments spread over a square, where spatial indices pay off
+pub fn create_short_segments_example(n_segments: usize, side: f64, length: f64, seed: u64) -> SweepLineProblem {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let segments = (0..n_segments).map(|_| {
+        let ini = Point { x: rng.gen::<f64>() * side, y: rng.gen::<f64>() * side };
+        let end = Point { x: ini.x + (rng.gen::<f64>() - 0.5) * length, y: ini.y + (rng.gen::<f64>() - 0.5) * length };
+        Segment { ini, end }
+    }).collect();
+    SweepLineProblem::new(segments)
 }
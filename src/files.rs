@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use crate::binary::{load_binary, save_binary};
+use crate::domain::{SweepLineProblem, DEFAULT_EXPECTED_SOLVER};
+use crate::dxf::{load_dxf, save_dxf};
+use crate::geojson::{load_geojson, save_geojson};
+use crate::solvers::{find_solver, same_intersections};
+use crate::svg::{save_svg, SvgOptions};
+use crate::wkt::{load_wkt, segments_to_wkt};
+
+// Extensions `load_problem` can read
+pub const PROBLEM_EXTENSIONS: [&str; 6] = ["txt", "swlb", "geojson", "json", "wkt", "dxf"];
+
+fn extension(path: &str) -> String {
+    Path::new(path).extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+// Reads a problem in the format given by the extension of the path
+pub fn load_problem(path: &str) -> Result<SweepLineProblem, String> {
+    if !Path::new(path).is_file() {
+        return Err(format!("{}: no such file", path));
+    }
+    match extension(path).as_str() {
+        "txt" => SweepLineProblem::load(path),
+        "swlb" => load_binary(path),
+        "geojson" | "json" => load_geojson(path),
+        "wkt" => load_wkt(path),
+        "dxf" => load_dxf(path),
+        other => Err(format!("{}: unknown problem format '{}'", path, other)),
+    }
+}
+
+// Writes the problem in the format given by the extension of the path. With `with_expected`
+// text files get the intersections of the sweep line solver as their expected section, the
+// problem files test then checks that the sweep still finds them. Without it an expected
+// section that was loaded is kept as long as its solver still finds it, so saving an edited
+// problem never writes a result that does not belong to it.
+pub fn save_problem(problem: &SweepLineProblem, path: &str, with_expected: bool) -> Result<(), String> {
+    if with_expected && extension(path) != "txt" {
+        return Err(format!("{}: only text files keep the expected intersections", path));
+    }
+    match extension(path).as_str() {
+        "txt" => {
+            let mut saved_problem = problem.clone();
+            if with_expected {
+                saved_problem.expected_solver = DEFAULT_EXPECTED_SOLVER.to_string();
+            }
+            if with_expected || saved_problem.expected.is_some() {
+                let solver = find_solver(&saved_problem.expected_solver)
+                    .ok_or_else(|| format!("{}: unknown solver '{}'", path, saved_problem.expected_solver))?;
+                solver(&mut saved_problem);
+                let found: Vec<_> = saved_problem.result.iter().cloned()
+                    .zip(saved_problem.result_pairs.iter().cloned())
+                    .collect();
+                let reproduced = saved_problem.expected.as_ref()
+                    .is_some_and(|expected| same_intersections(expected, &found));
+                if with_expected {
+                    saved_problem.record_expected(DEFAULT_EXPECTED_SOLVER);
+                } else if !reproduced {
+                    saved_problem.expected = None;
+                }
+            }
+            saved_problem.save(path)
+        }
+        "swlb" => save_binary(problem, path),
+        "geojson" | "json" => save_geojson(problem, path),
+        "wkt" => std::fs::write(path, segments_to_wkt(problem) + "\n")
+            .map_err(|error| format!("{}: {}", path, error)),
+        "dxf" => save_dxf(problem, path),
+        "svg" => save_svg(problem, path, &SvgOptions::default()),
+        other => Err(format!("{}: unknown problem format '{}'", path, other)),
+    }
+}
+
+// Orders names with numbers by their value, so that problem_2 comes before problem_10
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_digits = String::new();
+                while let Some(c) = a.peek().copied().filter(char::is_ascii_digit) {
+                    x_digits.push(c);
+                    a.next();
+                }
+                let mut y_digits = String::new();
+                while let Some(c) = b.peek().copied().filter(char::is_ascii_digit) {
+                    y_digits.push(c);
+                    b.next();
+                }
+                let x_value = x_digits.trim_start_matches('0');
+                let y_value = y_digits.trim_start_matches('0');
+                let order = x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+// Problem files of a directory, in natural order
+pub fn list_problem_files(directory: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(directory) else { return Vec::new() };
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| path.to_str().map(|path| path.to_string()))
+        .filter(|path| PROBLEM_EXTENSIONS.contains(&extension(path).as_str()))
+        .collect();
+    paths.sort_by(|a, b| natural_cmp(a, b));
+    paths
+}
+
+// First problems/sweep_line_problem_N.txt that does not exist
+pub fn next_free_problem_path() -> String {
+    let mut i = 0;
+    loop {
+        let path = format!("problems/sweep_line_problem_{}.txt", i);
+        if !Path::new(&path).exists() {
+            return path;
+        }
+        i += 1;
+    }
+}
+
+pub fn test_files() {
+    let directory = std::env::temp_dir().join("sweep_line_files");
+    std::fs::create_dir_all(&directory).unwrap();
+    let directory = directory.to_str().unwrap().to_string();
+    let problem = SweepLineProblem::load("problems/sweep_line_problem_0.txt").unwrap();
+    for extension in PROBLEM_EXTENSIONS {
+        let path = format!("{}/problem.{}", directory, extension);
+        save_problem(&problem, &path, false).unwrap();
+        let loaded = load_problem(&path).unwrap();
+        // Intersections written next to the segments by some formats are not read back
+        assert!(loaded.segments[..problem.segments.len()] == problem.segments[..], "{}", path);
+    }
+    save_problem(&problem, &format!("{}/problem.svg", directory), false).unwrap();
+    assert!(load_problem(&format!("{}/problem.svg", directory)).is_err());
+    assert!(load_problem(&format!("{}/missing.txt", directory)).is_err());
+    std::fs::write(format!("{}/broken.txt", directory), "version 2\nsegments 1\n1 2 x 4\n").unwrap();
+    let Err(error) = load_problem(&format!("{}/broken.txt", directory)) else { panic!() };
+    assert!(error.contains("not a number"), "{}", error);
+
+    let files = list_problem_files(&directory);
+    assert_eq!(files.len(), PROBLEM_EXTENSIONS.len() + 1);
+    assert!(natural_cmp("problem_2.txt", "problem_10.txt") == Ordering::Less);
+    assert!(natural_cmp("problem_02.txt", "problem_2.txt") == Ordering::Equal);
+    assert!(natural_cmp("a", "b") == Ordering::Less);
+    let problems = list_problem_files("problems");
+    let position = |name: &str| problems.iter().position(|path| path.ends_with(name)).unwrap();
+    assert!(position("sweep_line_problem_2.txt") < position("sweep_line_problem_10.txt"));
+    assert!(save_problem(&problem, &format!("{}/problem.unknown", directory), false).is_err());
+
+    // Plain saves write no expected section, or keep the one that was loaded while its
+    // solver still finds it. Asking for it records what the sweep finds.
+    let path = std::env::temp_dir().join("sweep_line_expected.txt");
+    let path = path.to_str().unwrap();
+    save_problem(&problem, path, false).unwrap();
+    assert_eq!(load_problem(path).unwrap().expected, None);
+    save_problem(&problem, path, true).unwrap();
+    let mut with_expected = load_problem(path).unwrap();
+    assert_eq!(with_expected.expected_solver, DEFAULT_EXPECTED_SOLVER);
+    let mut solved = problem.clone();
+    find_solver(DEFAULT_EXPECTED_SOLVER).unwrap()(&mut solved);
+    assert_eq!(with_expected.expected.as_ref().map(|expected| expected.len()), Some(solved.result.len()));
+    save_problem(&with_expected, path, false).unwrap();
+    assert_eq!(load_problem(path).unwrap().expected, with_expected.expected);
+    let segment = with_expected.segments[0].clone();
+    with_expected.segments[1] = segment;
+    save_problem(&with_expected, path, false).unwrap();
+    assert_eq!(load_problem(path).unwrap().expected, None);
+    assert!(save_problem(&problem, &format!("{}/expected.wkt", directory), true).is_err());
+}
@@ -0,0 +1,233 @@
+use serde_json::{json, Map, Value};
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem};
+use crate::solvers::naive_intersection_solver;
+
+fn parse_position(value: &Value) -> Result<Point, String> {
+    let coordinates = value.as_array()
+        .ok_or_else(|| format!("position must be an array, found {}", value))?;
+    match (coordinates.first().and_then(Value::as_f64), coordinates.get(1).and_then(Value::as_f64)) {
+        (Some(x), Some(y)) => Ok(Point { x, y }),
+        _ => Err(format!("position must have two numbers, found {}", value)),
+    }
+}
+
+fn parse_line_string(value: &Value) -> Result<Vec<Point>, String> {
+    value.as_array()
+        .ok_or_else(|| format!("line string coordinates must be an array, found {}", value))?
+        .iter()
+        .map(parse_position)
+        .collect()
+}
+
+// Lines of a geometry, other geometry types have none
+fn geometry_lines(geometry: &Value) -> Result<Vec<Vec<Point>>, String> {
+    let coordinates = &geometry["coordinates"];
+    match geometry["type"].as_str() {
+        Some("LineString") => Ok(vec![parse_line_string(coordinates)?]),
+        Some("MultiLineString") => coordinates.as_array()
+            .ok_or_else(|| "MultiLineString coordinates must be an array".to_string())?
+            .iter()
+            .map(parse_line_string)
+            .collect(),
+        Some("GeometryCollection") => {
+            let mut lines = Vec::new();
+            for geometry in geometry["geometries"].as_array().unwrap_or(&Vec::new()) {
+                lines.extend(geometry_lines(geometry)?);
+            }
+            Ok(lines)
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn feature_id(feature: &Value, index: usize) -> String {
+    match &feature["id"] {
+        Value::String(id) => id.clone(),
+        Value::Number(id) => id.to_string(),
+        _ => index.to_string(),
+    }
+}
+
+// Properties of a segment written by `to_geojson`
+fn exported_segment_info(properties: &Value) -> Result<SegmentInfo, String> {
+    let color = match &properties["color"] {
+        Value::Null => SegmentColor::default(),
+        color => color.as_str().and_then(SegmentColor::parse)
+            .ok_or_else(|| format!("unknown color {}", color))?,
+    };
+    let tags = match &properties["tags"] {
+        Value::Null => Vec::new(),
+        tags => tags.as_array()
+            .and_then(|tags| tags.iter().map(|tag| tag.as_str().map(str::to_string)).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| format!("tags must be an array of strings, found {}", tags))?,
+    };
+    Ok(SegmentInfo::new(color, properties["feature_id"].as_str().unwrap_or_default().to_string(), tags))
+}
+
+// Reads the LineString and MultiLineString features of a FeatureCollection, a single
+// Feature or a bare geometry. Every line is split into its segments, each one labelled
+// with the id of its feature (or the feature index when it has no id). Features written by
+// `to_geojson` get back their label, color and tags, and the ones with a `kind` other than
+// "segment", like the intersections and overlaps, are skipped.
+pub fn parse_geojson(text: &str) -> Result<SweepLineProblem, String> {
+    let root: Value = serde_json::from_str(text).map_err(|error| error.to_string())?;
+    let features = match root["type"].as_str() {
+        Some("FeatureCollection") => root["features"].as_array()
+            .ok_or_else(|| "FeatureCollection without features".to_string())?
+            .clone(),
+        Some("Feature") => vec![root],
+        Some(_) => vec![json!({ "type": "Feature", "geometry": root })],
+        None => return Err("missing GeoJSON type".to_string()),
+    };
+
+    let mut problem = SweepLineProblem::new(Vec::new());
+    for (index, feature) in features.iter().enumerate() {
+        let id = feature_id(feature, index);
+        let properties = &feature["properties"];
+        let info = match properties["kind"].as_str() {
+            None => SegmentInfo::new(SegmentColor::Red, id.clone(), Vec::new()),
+            Some("segment") => exported_segment_info(properties)
+                .map_err(|error| format!("feature {}: {}", id, error))?,
+            Some(_) => continue,
+        };
+        for line in geometry_lines(&feature["geometry"])
+            .map_err(|error| format!("feature {}: {}", id, error))? {
+            for pair in line.windows(2) {
+                problem.add_segment(
+                    Segment { ini: pair[0].clone(), end: pair[1].clone() },
+                    info.clone()
+                );
+            }
+        }
+    }
+    Ok(problem)
+}
+
+pub fn load_geojson(path: &str) -> Result<SweepLineProblem, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse_geojson(&text)
+}
+
+fn position(p: &Point) -> Value {
+    json!([p.x, p.y])
+}
+
+// FeatureCollection with the input segments as LineStrings and the result as Points, or
+// as LineStrings for overlaps. Intersections list the indices of their two segments and
+// the feature ids these came from.
+pub fn to_geojson(problem: &SweepLineProblem) -> Value {
+    let mut features = Vec::new();
+    for (i, (segment, info)) in problem.segments.iter().zip(problem.segment_info.iter()).enumerate() {
+        let mut properties = Map::new();
+        properties.insert("kind".to_string(), json!("segment"));
+        properties.insert("segment".to_string(), json!(i));
+        properties.insert("color".to_string(), json!(info.color.name()));
+        if !info.label().is_empty() {
+            properties.insert("feature_id".to_string(), json!(info.label()));
+        }
+        if !info.tags().is_empty() {
+            properties.insert("tags".to_string(), json!(info.tags()));
+        }
+        features.push(json!({
+            "type": "Feature",
+            "id": i,
+            "geometry": {
+                "type": "LineString",
+                "coordinates": [position(&segment.ini), position(&segment.end)],
+            },
+            "properties": properties,
+        }));
+    }
+    for (intersection, &(i, j)) in problem.result.iter().zip(problem.result_pairs.iter()) {
+        let (kind, geometry) = if intersection.ini == intersection.end {
+            ("intersection", json!({ "type": "Point", "coordinates": position(&intersection.ini) }))
+        } else {
+            ("overlap", json!({
+                "type": "LineString",
+                "coordinates": [position(&intersection.ini), position(&intersection.end)],
+            }))
+        };
+        features.push(json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": {
+                "kind": kind,
+                "segments": [i, j],
+                "feature_ids": [
+                    problem.segment_info[i].label().to_string(),
+                    problem.segment_info[j].label().to_string(),
+                ],
+            },
+        }));
+    }
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+pub fn save_geojson(problem: &SweepLineProblem, path: &str) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(&to_geojson(problem)).map_err(|error| error.to_string())?;
+    std::fs::write(path, text).map_err(|error| format!("{}: {}", path, error))
+}
+
+pub fn test_geojson() {
+    let text = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "id": "road-1",
+                "geometry": {"type": "LineString", "coordinates": [[0, 0], [10, 10], [20, 0]]},
+                "properties": {}
+            },
+            {
+                "type": "Feature",
+                "id": 7,
+                "geometry": {
+                    "type": "MultiLineString",
+                    "coordinates": [[[0, 5], [20, 5]], [[30, 30], [40, 40]]]
+                },
+                "properties": {}
+            },
+            {
+                "type": "Feature",
+                "id": "overlap-road",
+                "geometry": {"type": "LineString", "coordinates": [[6, 6], [9, 9]]},
+                "properties": {}
+            },
+            {
+                "type": "Feature",
+                "geometry": {"type": "Point", "coordinates": [1, 1]},
+                "properties": {}
+            }
+        ]
+    }"#;
+    let mut problem = parse_geojson(text).unwrap();
+    assert_eq!(problem.segments.len(), 5);
+    let labels: Vec<&str> = problem.segment_info.iter().map(|info| info.label()).collect();
+    assert_eq!(labels, vec!["road-1", "road-1", "7", "7", "overlap-road"]);
+    problem.segment_info[2].color = SegmentColor::Blue;
+    problem.segment_info[3].set_label(String::new());
+    problem.segment_info[4].set_tags(vec!["bridge".to_string()]);
+
+    naive_intersection_solver(&mut problem);
+    let exported = to_geojson(&problem);
+    let features = exported["features"].as_array().unwrap();
+    let points: Vec<&Value> = features.iter()
+        .filter(|feature| feature["properties"]["kind"] == "intersection")
+        .collect();
+    // The horizontal line crosses both legs of the first line, and the legs touch at the top
+    assert_eq!(points.len(), 3);
+    for point in points.iter().filter(|point| point["geometry"]["coordinates"][1] == 5.0) {
+        assert_eq!(point["properties"]["feature_ids"], json!(["road-1", "7"]));
+    }
+
+    assert_eq!(features.iter().filter(|feature| feature["properties"]["kind"] == "overlap").count(), 1);
+
+    // Exported segments read back as the same problem, with their labels, colors and tags,
+    // and the intersections and overlaps are not read as segments
+    let reloaded = parse_geojson(&exported.to_string()).unwrap();
+    assert!(reloaded.segments == problem.segments);
+    assert!(reloaded.segment_info == problem.segment_info);
+
+    assert!(parse_geojson("{\"type\": \"LineString\", \"coordinates\": [[0, \"a\"]]}").is_err());
+    assert!(parse_geojson("not json").is_err());
+}
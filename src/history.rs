@@ -0,0 +1,195 @@
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem};
+use crate::example_generator::create_seeded_example;
+
+// An edit of the segments that knows how to undo itself
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum EditCommand {
+    AddSegment { index: usize, segment: Segment, info: SegmentInfo },
+    RemoveSegment { index: usize, segment: Segment, info: SegmentInfo },
+    // `end` is 0 for ini and 1 for end
+    MoveEndpoint { index: usize, end: usize, from: Point, to: Point },
+    SetColor { index: usize, from: SegmentColor, to: SegmentColor },
+    // Applied in order and undone in reverse, as a single step of the history
+    Bulk(Vec<EditCommand>),
+}
+
+impl EditCommand {
+    pub fn apply(&self, problem: &mut SweepLineProblem) {
+        match self {
+            EditCommand::AddSegment { index, segment, info } => {
+                problem.segments.insert(*index, segment.clone());
+                problem.segment_info.insert(*index, info.clone());
+            }
+            EditCommand::RemoveSegment { index, .. } => {
+                problem.remove_segment(*index);
+            }
+            EditCommand::MoveEndpoint { index, end, to, .. } => {
+                set_endpoint(problem, *index, *end, to);
+            }
+            EditCommand::SetColor { index, to, .. } => {
+                problem.segment_info[*index].color = *to;
+            }
+            EditCommand::Bulk(commands) => {
+                for command in commands.iter() {
+                    command.apply(problem);
+                }
+            }
+        }
+    }
+
+    pub fn revert(&self, problem: &mut SweepLineProblem) {
+        match self {
+            EditCommand::AddSegment { index, .. } => {
+                problem.remove_segment(*index);
+            }
+            EditCommand::RemoveSegment { index, segment, info } => {
+                problem.segments.insert(*index, segment.clone());
+                problem.segment_info.insert(*index, info.clone());
+            }
+            EditCommand::MoveEndpoint { index, end, from, .. } => {
+                set_endpoint(problem, *index, *end, from);
+            }
+            EditCommand::SetColor { index, from, .. } => {
+                problem.segment_info[*index].color = *from;
+            }
+            EditCommand::Bulk(commands) => {
+                for command in commands.iter().rev() {
+                    command.revert(problem);
+                }
+            }
+        }
+    }
+}
+
+fn set_endpoint(problem: &mut SweepLineProblem, index: usize, end: usize, p: &Point) {
+    if end == 0 {
+        problem.segments[index].ini = p.clone();
+    } else {
+        problem.segments[index].end = p.clone();
+    }
+}
+
+// Removal of a segment, with what is needed to put it back
+pub fn remove_command(problem: &SweepLineProblem, index: usize) -> EditCommand {
+    EditCommand::RemoveSegment {
+        index,
+        segment: problem.segments[index].clone(),
+        info: problem.segment_info[index].clone(),
+    }
+}
+
+#[derive(Clone)]
+#[derive(Default)]
+pub struct EditHistory {
+    // Commands with the revision they lead to
+    done: Vec<(u64, EditCommand)>,
+    undone: Vec<(u64, EditCommand)>,
+    last_revision: u64,
+}
+
+impl EditHistory {
+    pub fn new() -> EditHistory {
+        EditHistory::default()
+    }
+
+    // Applies the command and makes it the last one to undo, dropping the redo history
+    pub fn apply(&mut self, command: EditCommand, problem: &mut SweepLineProblem) {
+        command.apply(problem);
+        self.record(command);
+    }
+
+    // Records a command whose effect is already in the problem, like a finished drag
+    pub fn record(&mut self, command: EditCommand) {
+        self.last_revision += 1;
+        self.done.push((self.last_revision, command));
+        self.undone.clear();
+    }
+
+    // Returns false if there is nothing to undo
+    pub fn undo(&mut self, problem: &mut SweepLineProblem) -> bool {
+        let Some((revision, command)) = self.done.pop() else { return false };
+        command.revert(problem);
+        self.undone.push((revision, command));
+        true
+    }
+
+    pub fn redo(&mut self, problem: &mut SweepLineProblem) -> bool {
+        let Some((revision, command)) = self.undone.pop() else { return false };
+        command.apply(problem);
+        self.done.push((revision, command));
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    // Identifies the state of the segments: undoing back to a state gives its revision
+    // again, while every new edit gets a revision never seen before. 0 is the starting state.
+    pub fn revision(&self) -> u64 {
+        self.done.last().map_or(0, |(revision, _)| *revision)
+    }
+}
+
+pub fn test_edit_history() {
+    let original = create_seeded_example(20, 11);
+    let mut problem = original.clone();
+    let mut history = EditHistory::new();
+    let mut states = vec![problem.clone()];
+    let same = |a: &SweepLineProblem, b: &SweepLineProblem| {
+        a.segments == b.segments && a.segment_info == b.segment_info
+    };
+
+    history.apply(EditCommand::AddSegment {
+        index: 20,
+        segment: Segment { ini: Point { x: 0.0, y: 0.0 }, end: Point { x: 10.0, y: 10.0 } },
+        info: SegmentInfo::new(SegmentColor::Red, "new".to_string(), Vec::new()),
+    }, &mut problem);
+    states.push(problem.clone());
+    history.apply(remove_command(&problem, 3), &mut problem);
+    states.push(problem.clone());
+    let from = problem.segments[5].end.clone();
+    history.apply(EditCommand::MoveEndpoint { index: 5, end: 1, from, to: Point { x: 1.0, y: 2.0 } }, &mut problem);
+    states.push(problem.clone());
+    let from = problem.segment_info[0].color;
+    history.apply(EditCommand::SetColor { index: 0, from, to: SegmentColor::Blue }, &mut problem);
+    states.push(problem.clone());
+    // Removing several segments at once, from the last so the indices stay valid
+    let bulk = EditCommand::Bulk(vec![remove_command(&problem, 10), remove_command(&problem, 2)]);
+    history.apply(bulk, &mut problem);
+    assert_eq!(problem.segments.len(), 18);
+    states.push(problem.clone());
+
+    for state in states.iter().rev().skip(1) {
+        assert!(history.undo(&mut problem));
+        assert!(same(&problem, state));
+    }
+    assert!(!history.undo(&mut problem));
+    assert!(same(&problem, &original));
+    for state in states.iter().skip(1) {
+        assert!(history.redo(&mut problem));
+        assert!(same(&problem, state));
+    }
+    assert!(!history.redo(&mut problem));
+
+    // A new edit after undoing drops what could have been redone
+    let last = history.revision();
+    history.undo(&mut problem);
+    let before_last = history.revision();
+    assert!(before_last != last);
+    history.redo(&mut problem);
+    assert_eq!(history.revision(), last);
+    history.undo(&mut problem);
+    history.apply(EditCommand::SetColor { index: 1, from: SegmentColor::Red, to: SegmentColor::Red }, &mut problem);
+    assert!(!history.can_redo());
+    assert!(history.can_undo());
+    assert!(history.revision() != last && history.revision() != before_last);
+    while history.undo(&mut problem) {}
+    assert_eq!(history.revision(), 0);
+}
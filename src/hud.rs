@@ -0,0 +1,160 @@
+use crate::domain::{distance, Point, Segment, SweepLineProblem};
+use crate::solvers::naive_intersection_solver;
+
+// How two segments meet at a reported intersection
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum IntersectionKind {
+    // Interiors cross
+    Crossing,
+    // An endpoint of one segment lies inside the other
+    Touching,
+    // Endpoint of both segments
+    SharedEndpoint,
+    // Collinear segments sharing more than a point
+    Overlap,
+}
+
+impl IntersectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntersectionKind::Crossing => "crossing",
+            IntersectionKind::Touching => "touching",
+            IntersectionKind::SharedEndpoint => "shared endpoint",
+            IntersectionKind::Overlap => "overlap",
+        }
+    }
+}
+
+fn is_endpoint(p: &Point, s: &Segment) -> bool {
+    *p == s.ini || *p == s.end
+}
+
+// Kind of the k-th intersection of the result
+pub fn classify_intersection(problem: &SweepLineProblem, k: usize) -> IntersectionKind {
+    let intersection = &problem.result[k];
+    if intersection.ini != intersection.end {
+        return IntersectionKind::Overlap;
+    }
+    let (i, j) = problem.result_pairs[k];
+    let p = &intersection.ini;
+    match (is_endpoint(p, &problem.segments[i]), is_endpoint(p, &problem.segments[j])) {
+        (true, true) => IntersectionKind::SharedEndpoint,
+        (false, false) => IntersectionKind::Crossing,
+        _ => IntersectionKind::Touching,
+    }
+}
+
+// Closest intersection of the result within `radius` of the point
+pub fn intersection_under(problem: &SweepLineProblem, p: &Point, radius: f64) -> Option<usize> {
+    let mut closest = None;
+    let mut closest_distance = radius;
+    for (k, intersection) in problem.result.iter().enumerate() {
+        let d = f64::min(distance(p, &intersection.ini), distance(p, &intersection.end));
+        if d <= closest_distance {
+            closest = Some(k);
+            closest_distance = d;
+        }
+    }
+    closest
+}
+
+fn format_point(p: &Point) -> String {
+    format!("({}, {})", p.x, p.y)
+}
+
+fn format_segment(s: &Segment) -> String {
+    format!("{} - {}", format_point(&s.ini), format_point(&s.end))
+}
+
+// What the solver of the frame did
+pub struct SolveStats {
+    pub solver: String,
+    pub basic_operations: i32,
+    // Seconds, None when nothing was solved in the frame
+    pub time: Option<f64>,
+}
+
+// Lines of the overlay: counts, solver statistics, the cursor and what is under it. The
+// hovered endpoint is given as (segment index, 0 for ini or 1 for end).
+pub fn hud_lines(
+    problem: &SweepLineProblem,
+    stats: &SolveStats,
+    mouse: &Point,
+    hovered_endpoint: Option<(usize, usize)>,
+    hovered_intersection: Option<usize>,
+) -> Vec<String> {
+    let mut lines = vec![
+        format!("{} segments, {} intersections", problem.segments.len(), problem.result.len()),
+        match stats.time {
+            Some(time) => format!(
+                "{}: {} basic operations, {:.3} ms", stats.solver, stats.basic_operations, time * 1000.0
+            ),
+            None => format!("{}: {} basic operations", stats.solver, stats.basic_operations),
+        },
+        format!("cursor {}", format_point(mouse)),
+    ];
+    if let Some((i, end)) = hovered_endpoint {
+        let info = &problem.segment_info[i];
+        let mut line = format!(
+            "segment {}{}: {}, {} endpoint, {}",
+            i,
+            if info.label().is_empty() { String::new() } else { format!(" '{}'", info.label()) },
+            format_segment(&problem.segments[i]),
+            if end == 0 { "first" } else { "second" },
+            info.color.name(),
+        );
+        if !info.tags().is_empty() {
+            line.push_str(&format!(", tags {}", info.tags().join(" ")));
+        }
+        lines.push(line);
+    }
+    if let Some(k) = hovered_intersection {
+        let intersection = &problem.result[k];
+        let (i, j) = problem.result_pairs[k];
+        let position = if intersection.ini == intersection.end {
+            format_point(&intersection.ini)
+        } else {
+            format_segment(intersection)
+        };
+        lines.push(format!(
+            "intersection {}: {} of segments {} and {}, {}",
+            k, classify_intersection(problem, k).name(), i, j, position
+        ));
+    }
+    lines
+}
+
+pub fn test_hud() {
+    let point = |x: f64, y: f64| Point { x, y };
+    let mut problem = SweepLineProblem::new(vec![
+        Segment { ini: point(0.0, 0.0), end: point(10.0, 10.0) },
+        Segment { ini: point(0.0, 10.0), end: point(10.0, 0.0) },
+        Segment { ini: point(10.0, 10.0), end: point(20.0, 10.0) },
+        Segment { ini: point(15.0, 10.0), end: point(15.0, 20.0) },
+        Segment { ini: point(30.0, 0.0), end: point(40.0, 0.0) },
+        Segment { ini: point(35.0, 0.0), end: point(50.0, 0.0) },
+    ]);
+    naive_intersection_solver(&mut problem);
+    let kind_of = |problem: &SweepLineProblem, i: usize, j: usize| {
+        let k = problem.result_pairs.iter().position(|&pair| pair == (i, j)).unwrap();
+        classify_intersection(problem, k)
+    };
+    assert_eq!(kind_of(&problem, 0, 1), IntersectionKind::Crossing);
+    assert_eq!(kind_of(&problem, 0, 2), IntersectionKind::SharedEndpoint);
+    assert_eq!(kind_of(&problem, 2, 3), IntersectionKind::Touching);
+    assert_eq!(kind_of(&problem, 4, 5), IntersectionKind::Overlap);
+
+    let k = intersection_under(&problem, &point(5.5, 4.5), 1.0).unwrap();
+    assert_eq!(problem.result_pairs[k], (0, 1));
+    assert!(intersection_under(&problem, &point(5.0, 8.0), 1.0).is_none());
+
+    let stats = SolveStats { solver: "naive".to_string(), basic_operations: 15, time: Some(0.002) };
+    let lines = hud_lines(&problem, &stats, &point(5.5, 4.5), Some((2, 1)), Some(k));
+    assert_eq!(lines[0], "6 segments, 4 intersections");
+    assert_eq!(lines[1], "naive: 15 basic operations, 2.000 ms");
+    assert_eq!(lines[2], "cursor (5.5, 4.5)");
+    assert!(lines[3].starts_with("segment 2: (10, 10) - (20, 10), second endpoint"), "{}", lines[3]);
+    assert_eq!(lines[4], "intersection 0: crossing of segments 0 and 1, (5, 5)");
+}
@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::broad_phase::cell_size_for;
+use crate::domain::{segments_intersection, Point, Segment, SegmentColor, SweepLineProblem};
+use crate::example_generator::{create_seeded_example, create_short_segments_example};
+use crate::solvers::{naive_intersection_solver, red_blue_intersection_solver, same_intersections, sweep_line_solver};
+
+// A segment whose bounding box covers more cells than this is not put in the cells, it is
+// kept in a list and tested against every other segment. One endpoint dragged far away
+// would otherwise fill the grid.
+const MAX_CELLS_PER_SEGMENT: i128 = 256;
+
+// Buckets of segment indices by the square cells their bounding boxes cover
+#[derive(Clone)]
+pub struct SpatialGrid {
+    pub cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    // Segments that cover too many cells, in the order they were inserted
+    oversized: Vec<usize>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> SpatialGrid {
+        SpatialGrid { cell_size, cells: HashMap::new(), oversized: Vec::new() }
+    }
+
+    // Cells sized like the broad phase grid, so a typical segment covers a few of them
+    pub fn for_segments(segments: &[Segment]) -> SpatialGrid {
+        SpatialGrid::with_cell_size(segments, cell_size_for(segments))
+    }
+
+    pub fn with_cell_size(segments: &[Segment], cell_size: f64) -> SpatialGrid {
+        let mut grid = SpatialGrid::new(if cell_size > 0.0 { cell_size } else { 1.0 });
+        for (i, segment) in segments.iter().enumerate() {
+            grid.insert(i, segment);
+        }
+        grid
+    }
+
+    // First and last cell the bounding box covers, along x and along y
+    pub fn cell_range(&self, segment: &Segment) -> ((i64, i64), (i64, i64)) {
+        let cell = |v: f64| (v / self.cell_size).floor() as i64;
+        (
+            (cell(f64::min(segment.ini.x, segment.end.x)), cell(f64::max(segment.ini.x, segment.end.x))),
+            (cell(f64::min(segment.ini.y, segment.end.y)), cell(f64::max(segment.ini.y, segment.end.y))),
+        )
+    }
+
+    pub fn is_oversized(&self, segment: &Segment) -> bool {
+        let ((x_min, x_max), (y_min, y_max)) = self.cell_range(segment);
+        let count = |min: i64, max: i64| max as i128 - min as i128 + 1;
+        count(x_min, x_max) * count(y_min, y_max) > MAX_CELLS_PER_SEGMENT
+    }
+
+    fn cells_of(&self, segment: &Segment) -> Vec<(i64, i64)> {
+        let ((x_min, x_max), (y_min, y_max)) = self.cell_range(segment);
+        let mut cells = Vec::new();
+        for x in x_min..=x_max {
+            for y in y_min..=y_max {
+                cells.push((x, y));
+            }
+        }
+        cells
+    }
+
+    pub fn insert(&mut self, i: usize, segment: &Segment) {
+        if self.is_oversized(segment) {
+            self.oversized.push(i);
+            return;
+        }
+        for cell in self.cells_of(segment) {
+            self.cells.entry(cell).or_default().push(i);
+        }
+    }
+
+    // The segment has to be the one that was inserted with this index
+    pub fn remove(&mut self, i: usize, segment: &Segment) {
+        if self.is_oversized(segment) {
+            self.oversized.retain(|&j| j != i);
+            return;
+        }
+        for cell in self.cells_of(segment) {
+            if let Some(bucket) = self.cells.get_mut(&cell) {
+                bucket.retain(|&j| j != i);
+                if bucket.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    // Segments that are in no cell, every pair with one of them has to be tested
+    pub fn oversized(&self) -> &[usize] {
+        &self.oversized
+    }
+
+    // Every cell that holds a segment, with the segments in it
+    pub fn buckets(&self) -> impl Iterator<Item = (&(i64, i64), &Vec<usize>)> {
+        self.cells.iter()
+    }
+
+    // Segments sharing a cell with the given one and the oversized ones, in increasing order
+    // and without repeats. An oversized segment gets every segment in the grid.
+    pub fn candidates(&self, segment: &Segment) -> Vec<usize> {
+        let mut candidates = self.oversized.clone();
+        if self.is_oversized(segment) {
+            for bucket in self.cells.values() {
+                candidates.extend(bucket.iter().copied());
+            }
+        } else {
+            for cell in self.cells_of(segment) {
+                if let Some(bucket) = self.cells.get(&cell) {
+                    candidates.extend(bucket.iter().copied());
+                }
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+// Keeps the intersections of a problem up to date while segments move, testing a moved
+// segment only against the segments near it. Adding or removing segments shifts the
+// indices, so that needs a new solver. In red-blue mode only pairs of segments of different
+// colors are reported, like the red-blue solver does.
+#[derive(Clone)]
+pub struct IncrementalSolver {
+    grid: SpatialGrid,
+    // Segments as they are in the grid
+    segments: Vec<Segment>,
+    // Intersections by pair of segment indices, the smaller first
+    intersections: BTreeMap<(usize, usize), Segment>,
+    red_blue: bool,
+    colors: Vec<SegmentColor>,
+}
+
+impl IncrementalSolver {
+    pub fn new(problem: &mut SweepLineProblem, red_blue: bool) -> IncrementalSolver {
+        let mut solver = IncrementalSolver {
+            grid: SpatialGrid::for_segments(&problem.segments),
+            segments: problem.segments.clone(),
+            intersections: BTreeMap::new(),
+            red_blue,
+            colors: problem.segment_info.iter().map(|info| info.color).collect(),
+        };
+        problem.basic_operations = 0;
+        for i in 0..solver.segments.len() {
+            solver.find_intersections(i, |j| j > i, &mut problem.basic_operations);
+        }
+        solver.write_result(problem);
+        solver
+    }
+
+    fn find_intersections(&mut self, i: usize, wanted: impl Fn(usize) -> bool, basic_operations: &mut i32) {
+        for j in self.grid.candidates(&self.segments[i]) {
+            if j == i || !wanted(j) || (self.red_blue && self.colors[i] == self.colors[j]) {
+                continue;
+            }
+            *basic_operations += 1;
+            if let Some(intersection) = segments_intersection(&self.segments[i], &self.segments[j]) {
+                self.intersections.insert((usize::min(i, j), usize::max(i, j)), intersection);
+            }
+        }
+    }
+
+    fn write_result(&self, problem: &mut SweepLineProblem) {
+        problem.result = self.intersections.values().cloned().collect();
+        problem.result_pairs = self.intersections.keys().copied().collect();
+    }
+
+    // Updates the intersections of the segments at the given indices, which have moved since
+    // the last update. basic_operations counts the pairs tested by this update.
+    pub fn update_segments(&mut self, problem: &mut SweepLineProblem, moved: &[usize]) {
+        let mut moved = moved.to_vec();
+        moved.sort();
+        moved.dedup();
+        problem.basic_operations = 0;
+        for &i in moved.iter() {
+            let old = std::mem::replace(&mut self.segments[i], problem.segments[i].clone());
+            self.colors[i] = problem.segment_info[i].color;
+            self.grid.remove(i, &old);
+            self.grid.insert(i, &self.segments[i]);
+            self.intersections.retain(|&(a, b), _| a != i && b != i);
+        }
+        for (k, &i) in moved.iter().enumerate() {
+            // Pairs of two moved segments are tested once, by the first of them
+            let earlier = &moved[..k];
+            self.find_intersections(i, |j| !earlier.contains(&j), &mut problem.basic_operations);
+        }
+        self.write_result(problem);
+    }
+}
+
+pub fn test_incremental_solver() {
+    let pairs = |problem: &SweepLineProblem| -> Vec<(Segment, (usize, usize))> {
+        problem.result.iter().cloned().zip(problem.result_pairs.iter().cloned()).collect()
+    };
+    let mut rng = StdRng::seed_from_u64(9);
+    for (mut problem, red_blue) in [
+        (create_seeded_example(200, 8), false),
+        (create_short_segments_example(500, 1000.0, 60.0, 4), false),
+        (create_seeded_example(200, 3), true),
+    ] {
+        let reference_solver = if red_blue { red_blue_intersection_solver } else { naive_intersection_solver };
+        let mut solver = IncrementalSolver::new(&mut problem, red_blue);
+        let mut reference = problem.clone();
+        reference_solver(&mut reference);
+        assert!(same_intersections(&pairs(&problem), &pairs(&reference)));
+
+        // Dragging single segments and groups, as the viewer does
+        for round in 0..50 {
+            let moved: Vec<usize> = (0..1 + round % 3).map(|_| rng.gen_range(0..problem.segments.len())).collect();
+            let mut moved_once = moved.clone();
+            moved_once.sort();
+            moved_once.dedup();
+            let (dx, dy) = (rng.gen::<f64>() * 100.0 - 50.0, rng.gen::<f64>() * 100.0 - 50.0);
+            for &i in moved_once.iter() {
+                let segment = &mut problem.segments[i];
+                segment.end = Point { x: segment.end.x + dx, y: segment.end.y + dy };
+                if round % 2 == 0 {
+                    segment.ini = Point { x: segment.ini.x + dx, y: segment.ini.y + dy };
+                }
+                // Recolored segments are updated like moved ones
+                if round % 5 == 0 {
+                    let info = &mut problem.segment_info[i];
+                    info.color = if info.color == SegmentColor::Red { SegmentColor::Blue } else { SegmentColor::Red };
+                }
+            }
+            solver.update_segments(&mut problem, &moved);
+            let mut reference = problem.clone();
+            reference_solver(&mut reference);
+            assert!(same_intersections(&pairs(&problem), &pairs(&reference)), "round {}", round);
+        }
+    }
+
+    // An endpoint dragged very far away, as after zooming out, leaves the grid as small as
+    // it was and the segment is tested against all the others
+    let mut problem = create_short_segments_example(2000, 1000.0, 60.0, 2);
+    let mut solver = IncrementalSolver::new(&mut problem, false);
+    let n_cells = solver.grid.buckets().count();
+    for end in [Point { x: 1e6, y: -1e6 }, Point { x: 500.0, y: 500.0 }, Point { x: -1e300, y: 1e300 }] {
+        problem.segments[3].end = end;
+        solver.update_segments(&mut problem, &[3]);
+        assert!(solver.grid.buckets().count() <= n_cells + MAX_CELLS_PER_SEGMENT as usize);
+        let mut reference = problem.clone();
+        naive_intersection_solver(&mut reference);
+        assert!(same_intersections(&pairs(&problem), &pairs(&reference)));
+    }
+    assert_eq!(solver.grid.oversized(), &[3]);
+    problem.segments[3].end = problem.segments[3].ini.clone();
+    solver.update_segments(&mut problem, &[3]);
+    assert!(solver.grid.oversized().is_empty());
+
+    // One segment dragged among many
+    let mut problem = create_short_segments_example(5000, 2000.0, 60.0, 1);
+    let start = Instant::now();
+    sweep_line_solver(&mut problem.clone());
+    let sweep_time = start.elapsed().as_secs_f64();
+    let mut solver = IncrementalSolver::new(&mut problem, false);
+    let start = Instant::now();
+    for k in 0..100 {
+        problem.segments[7].end.x += if k % 2 == 0 { 5.0 } else { -5.0 };
+        solver.update_segments(&mut problem, &[7]);
+    }
+    let update_time = start.elapsed().as_secs_f64() / 100.0;
+    println!(
+        "5000 segments: full sweep {:.3} ms, incremental update {:.3} ms, {} pairs tested",
+        sweep_time * 1000.0, update_time * 1000.0, problem.basic_operations
+    );
+}
@@ -2,15 +2,155 @@ mod example_generator;
 mod domain;
 mod visualization;
 mod solvers;
+mod polygon;
+mod arrangement;
+mod noding;
+mod geojson;
+mod wkt;
+mod svg;
+mod scene;
+mod raster;
+mod binary;
+mod dxf;
+mod trace;
+mod camera;
+mod history;
+mod files;
+mod selection;
+mod hud;
+mod incremental;
+mod parallel;
+mod broad_phase;
+mod recording;
+mod settings;
 
 use rand;
 use std::cmp::PartialEq;
-use domain::{Point, Segment, Direction, SweepLineProblem, segments_intersection, test_treap};
-use solvers::test_sweep_line_solver;
+use domain::{Point, Segment, Direction, SweepLineProblem, segments_intersection, test_treap, test_problem_file};
+use solvers::{test_sweep_line_solver, test_red_blue_solver, test_problem_files, test_sweep_line_stepper, test_compare_solvers, benchmark_solvers};
+use polygon::test_polygon;
+use arrangement::test_arrangement;
+use noding::{test_noding, test_snap_rounding};
+use geojson::test_geojson;
+use wkt::test_wkt;
+use svg::test_svg;
+use raster::test_golden_images;
+use binary::{test_binary, benchmark_load, convert};
+use dxf::test_dxf;
+use trace::{test_trace, load_trace, save_trace, TraceReplayer};
+use camera::test_camera;
+use history::test_edit_history;
+use files::test_files;
+use selection::test_selection;
+use hud::test_hud;
+use incremental::test_incremental_solver;
+use parallel::{test_parallel_solver, benchmark_parallel_solver};
+use broad_phase::test_broad_phase_solvers;
+use recording::{test_recording, export_session, load_session, RecordingOptions};
+use settings::test_settings;
 use visualization::visualization;
 
 fn main() {
     //test_treap();
+    //test_problem_file();
+    //test_problem_files();
     //test_sweep_line_solver();
-    visualization().unwrap();
+    //test_red_blue_solver();
+    //test_sweep_line_stepper();
+    //test_compare_solvers();
+    //test_polygon();
+    //test_arrangement();
+    //test_noding();
+    //test_snap_rounding();
+    //test_geojson();
+    //test_wkt();
+    //test_svg();
+    //test_golden_images();
+    //test_binary();
+    //benchmark_load();
+    //test_dxf();
+    //test_trace();
+    //test_camera();
+    //test_edit_history();
+    //test_files();
+    //test_selection();
+    //test_hud();
+    //test_incremental_solver();
+    //test_parallel_solver();
+    //benchmark_parallel_solver();
+    //test_broad_phase_solvers();
+    //benchmark_solvers();
+    //test_recording();
+    //test_settings();
+
+    // sweep_line convert <input> <output> converts between the text and binary formats
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 4 && args[1] == "convert" {
+        if let Err(error) = convert(&args[2], &args[3]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // sweep_line trace <problem> <trace.jsonl> writes the trace of the sweep line solver
+    if args.len() == 4 && args[1] == "trace" {
+        let mut problem = match SweepLineProblem::load(&args[2]) {
+            Ok(problem) => problem,
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        };
+        if let Err(error) = save_trace(&solvers::traced_sweep_line_solver(&mut problem), &args[3]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // sweep_line replay <trace.jsonl> <step> prints the state of the sweep after that step
+    if args.len() == 4 && args[1] == "replay" {
+        let replayer = match load_trace(&args[2]) {
+            Ok(trace) => TraceReplayer::new(trace),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        };
+        let steps = match args[3].parse() {
+            Ok(steps) => steps,
+            Err(_) => {
+                eprintln!("invalid step '{}', expected a number from 0 to {}", args[3], replayer.n_steps);
+                std::process::exit(1);
+            }
+        };
+        let state = replayer.state_at(steps);
+        println!("step {} of {}, sweep at x = {:?}", state.steps, replayer.n_steps, state.sweep_x);
+        println!("last event: {:?}", state.last_event);
+        println!("status: {:?}", state.status);
+        println!("{} pending events, {} intersections", state.pending.len(), state.result.len());
+        return;
+    }
+    // sweep_line render <session.jsonl> <out.gif | frame directory> [fps] [WIDTHxHEIGHT] renders
+    // a session recorded in the viewer
+    if args.len() >= 4 && args[1] == "render" {
+        // A frame rate or size that does not parse is an error, not the default
+        let options = RecordingOptions::parse(args.get(4).map(String::as_str), args.get(5).map(String::as_str));
+        let rendered = options.and_then(|options| {
+            let session = load_session(&args[2])?;
+            export_session(&session, &options, &args[3])
+        });
+        match rendered {
+            Ok(n_frames) => println!("{} frames written to {}", n_frames, args[3]),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // sweep_line <problem> opens that file in the viewer
+    if let Err(error) = visualization(args.get(1).cloned()) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use crate::domain::{Point, Segment, Direction, SweepLineProblem, distance, orientation};
+use crate::example_generator::create_random_example;
+use crate::solvers::naive_intersection_solver;
+
+// Points closer than this are merged into the same node, intersection points of
+// several segments through the same place rarely come out bit for bit equal
+pub const NODE_TOLERANCE: f64 = 1e-7;
+
+// Piece of an input segment that only touches other pieces at its endpoints
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct NodedSegment {
+    pub segment: Segment,
+    // Index of the input segment this piece comes from
+    pub original: usize,
+}
+
+fn node_key(p: &Point) -> (i64, i64) {
+    ((p.x / NODE_TOLERANCE).round() as i64, (p.y / NODE_TOLERANCE).round() as i64)
+}
+
+// Existing node within the tolerance of the point, or the point itself as a new node.
+// Neighbouring keys are checked too, two close points can round to different keys.
+fn find_node(nodes: &mut HashMap<(i64, i64), Point>, p: &Point) -> Point {
+    let (kx, ky) = node_key(p);
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if let Some(node) = nodes.get(&(kx + dx, ky + dy)) {
+                if distance(node, p) <= NODE_TOLERANCE {
+                    return node.clone();
+                }
+            }
+        }
+    }
+    nodes.insert((kx, ky), p.clone());
+    p.clone()
+}
+
+// Position of a point along the segment, 0 at ini and 1 at end
+fn parameter(s: &Segment, p: &Point) -> f64 {
+    let dx = s.end.x - s.ini.x;
+    let dy = s.end.y - s.ini.y;
+    let length = dx * dx + dy * dy;
+    if length == 0.0 {
+        return 0.0;
+    }
+    ((p.x - s.ini.x) * dx + (p.y - s.ini.y) * dy) / length
+}
+
+// Splits every input segment at the intersection points found by a solver, `result` and
+// `result_pairs` must already be filled. Pieces keep the direction of their original
+// segment, nodes shared by several pieces get exactly the same coordinates and
+// zero-length segments are dropped. Overlapping segments give one piece each.
+pub fn node_segments(problem: &SweepLineProblem) -> Vec<NodedSegment> {
+    let mut points_on_segment: Vec<Vec<Point>> = problem.segments.iter()
+        .map(|segment| vec![segment.ini.clone(), segment.end.clone()])
+        .collect();
+    for (intersection, &(i, j)) in problem.result.iter().zip(problem.result_pairs.iter()) {
+        for k in [i, j] {
+            points_on_segment[k].push(intersection.ini.clone());
+            if intersection.ini != intersection.end {
+                points_on_segment[k].push(intersection.end.clone());
+            }
+        }
+    }
+
+    let mut nodes: HashMap<(i64, i64), Point> = HashMap::new();
+    let mut pieces = Vec::new();
+    for (k, points) in points_on_segment.iter_mut().enumerate() {
+        let segment = &problem.segments[k];
+        points.sort_by(|a, b| {
+            parameter(segment, a).partial_cmp(&parameter(segment, b)).unwrap()
+        });
+        let mut previous: Option<Point> = None;
+        for point in points.iter() {
+            let node = find_node(&mut nodes, point);
+            if let Some(previous) = previous {
+                if previous != node {
+                    pieces.push(NodedSegment {
+                        segment: Segment { ini: previous, end: node.clone() },
+                        original: k,
+                    });
+                }
+            }
+            previous = Some(node);
+        }
+    }
+    pieces
+}
+
+// Grid cell of size `grid_size` centered on a grid point, as in `Point::to_grid`
+fn pixel(p: &Point, grid_size: f64) -> (i64, i64) {
+    ((p.x / grid_size).round() as i64, (p.y / grid_size).round() as i64)
+}
+
+// Parameters where the segment enters and leaves the pixel, or None if it misses it
+// (Liang-Barsky clipping)
+fn clip_to_pixel(s: &Segment, pixel: (i64, i64), grid_size: f64) -> Option<(f64, f64)> {
+    let half = grid_size / 2.0;
+    let cx = pixel.0 as f64 * grid_size;
+    let cy = pixel.1 as f64 * grid_size;
+    let dx = s.end.x - s.ini.x;
+    let dy = s.end.y - s.ini.y;
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    for (direction, distance_to_side) in [
+        (-dx, s.ini.x - (cx - half)),
+        (dx, (cx + half) - s.ini.x),
+        (-dy, s.ini.y - (cy - half)),
+        (dy, (cy + half) - s.ini.y),
+    ] {
+        if direction == 0.0 {
+            if distance_to_side < 0.0 {
+                return None;
+            }
+        } else {
+            let t = distance_to_side / direction;
+            if direction < 0.0 { t0 = t0.max(t); } else { t1 = t1.min(t); }
+        }
+    }
+    if t0 <= t1 { Some((t0, t1)) } else { None }
+}
+
+// Snap rounding: every endpoint and intersection point makes its grid pixel "hot", then each
+// segment is replaced by the chain of centers of the hot pixels it goes through. The output
+// vertices all lie on the grid and the pieces only meet at their endpoints, snapping never
+// makes two pieces cross. `result` and `result_pairs` must already be filled. The grid size
+// has to be positive and finite, otherwise there are no pixels to snap to.
+pub fn snap_round(problem: &SweepLineProblem, grid_size: f64) -> Result<Vec<NodedSegment>, String> {
+    if !(grid_size.is_finite() && grid_size > 0.0) {
+        return Err(format!("grid size must be positive, found {}", grid_size));
+    }
+    let mut hot_pixels: HashMap<i64, Vec<i64>> = HashMap::new();
+    let mut add_hot_pixel = |p: &Point| {
+        let (px, py) = pixel(p, grid_size);
+        let column = hot_pixels.entry(px).or_default();
+        if !column.contains(&py) {
+            column.push(py);
+        }
+    };
+    for segment in problem.segments.iter() {
+        add_hot_pixel(&segment.ini);
+        add_hot_pixel(&segment.end);
+    }
+    for intersection in problem.result.iter() {
+        add_hot_pixel(&intersection.ini);
+        add_hot_pixel(&intersection.end);
+    }
+
+    let mut pieces = Vec::new();
+    for (k, segment) in problem.segments.iter().enumerate() {
+        let (first_column, _) = pixel(&segment.ini, grid_size);
+        let (last_column, _) = pixel(&segment.end, grid_size);
+        let mut crossed = Vec::new();
+        for px in first_column.min(last_column)..=first_column.max(last_column) {
+            let Some(column) = hot_pixels.get(&px) else { continue };
+            for &py in column.iter() {
+                if let Some((enter, leave)) = clip_to_pixel(segment, (px, py), grid_size) {
+                    crossed.push((enter, leave, px, py));
+                }
+            }
+        }
+        // A segment can enter two pixels at once through their common side, the one it
+        // leaves first comes first
+        crossed.sort_by(|a, b| {
+            a.0.partial_cmp(&b.0).unwrap().then_with(|| a.1.partial_cmp(&b.1).unwrap())
+        });
+
+        let mut previous: Option<Point> = None;
+        for (_, _, px, py) in crossed {
+            let center = Point { x: px as f64 * grid_size, y: py as f64 * grid_size };
+            if let Some(previous) = previous {
+                if previous != center {
+                    pieces.push(NodedSegment {
+                        segment: Segment { ini: previous, end: center.clone() },
+                        original: k,
+                    });
+                }
+            }
+            previous = Some(center);
+        }
+    }
+    Ok(pieces)
+}
+
+// Problem made of the pieces, each one keeps the info of its original segment
+pub fn noded_problem(problem: &SweepLineProblem, pieces: &[NodedSegment]) -> SweepLineProblem {
+    let mut noded = SweepLineProblem::new(Vec::new());
+    for piece in pieces {
+        noded.add_segment(piece.segment.clone(), problem.segment_info[piece.original].clone());
+    }
+    noded
+}
+
+pub fn test_noding() {
+    for _ in 0..100 {
+        let mut problem = create_random_example(20);
+        naive_intersection_solver(&mut problem);
+        let pieces = node_segments(&problem);
+
+        // Pieces only meet at their endpoints. Consecutive pieces of the same segment are
+        // skipped, they are only collinear up to rounding and that confuses the check.
+        let mut noded = noded_problem(&problem, &pieces);
+        naive_intersection_solver(&mut noded);
+        for (intersection, &(i, j)) in noded.result.iter().zip(noded.result_pairs.iter()) {
+            if pieces[i].original == pieces[j].original {
+                continue;
+            }
+            let ends = [&noded.segments[i].ini, &noded.segments[i].end];
+            let overlap = noded.segments[i] == noded.segments[j] || noded.segments[i] == Segment {
+                ini: noded.segments[j].end.clone(), end: noded.segments[j].ini.clone()
+            };
+            assert!(overlap || (intersection.ini == intersection.end
+                && ends.iter().any(|end| distance(end, &intersection.ini) < 1e-6)));
+        }
+
+        // And together they cover the whole original segment
+        for (k, segment) in problem.segments.iter().enumerate() {
+            let length: f64 = pieces.iter()
+                .filter(|piece| piece.original == k)
+                .map(|piece| distance(&piece.segment.ini, &piece.segment.end))
+                .sum();
+            assert!((length - distance(&segment.ini, &segment.end)).abs() < 1e-6);
+        }
+    }
+}
+
+pub fn test_snap_rounding() {
+    let grid_size = 10.0;
+    for _ in 0..100 {
+        let mut problem = create_random_example(20);
+        naive_intersection_solver(&mut problem);
+        let pieces = snap_round(&problem, grid_size).unwrap();
+
+        for piece in pieces.iter() {
+            for p in [&piece.segment.ini, &piece.segment.end] {
+                assert_eq!(*p, p.to_grid(grid_size));
+            }
+        }
+
+        // Grid coordinates are small integers here so the orientation tests are exact,
+        // no two pieces may cross through their interiors
+        for i in 0..pieces.len() {
+            for j in i + 1..pieces.len() {
+                let s1 = &pieces[i].segment;
+                let s2 = &pieces[j].segment;
+                let o1 = orientation(&s2.ini, s1);
+                let o2 = orientation(&s2.end, s1);
+                let o3 = orientation(&s1.ini, s2);
+                let o4 = orientation(&s1.end, s2);
+                let touching = [&o1, &o2, &o3, &o4].iter().any(|o| **o == Direction::Collinear);
+                assert!(touching || o1 == o2 || o3 == o4);
+            }
+        }
+
+        // Snapped coordinates survive a save and load untouched
+        let snapped = noded_problem(&problem, &pieces);
+        let path = std::env::temp_dir().join("sweep_line_snap_rounding.txt");
+        snapped.save(path.to_str().unwrap()).unwrap();
+        let loaded = SweepLineProblem::load(path.to_str().unwrap()).unwrap();
+        assert!(loaded.segments == snapped.segments);
+    }
+
+    let problem = create_random_example(5);
+    for grid_size in [0.0, -10.0, f64::NAN, f64::INFINITY] {
+        assert!(snap_round(&problem, grid_size).is_err());
+    }
+}
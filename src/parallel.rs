@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use crate::broad_phase::grid_intersection_solver;
+use crate::domain::{segments_intersection, Point, Segment, SweepLineProblem};
+use crate::example_generator::{create_seeded_example, create_short_segments_example};
+use crate::solvers::{naive_intersection_solver, same_intersections, sweep_line_solver, Solver};
+
+// A vertical band of the plane with the pieces of the segments that cross it
+struct Slab {
+    pieces: Vec<Segment>,
+    // Index of the original segment of every piece
+    segments: Vec<usize>,
+}
+
+// Boundaries between `n_slabs` slabs with about the same number of endpoints in each. They
+// fall halfway between endpoint coordinates, so no endpoint lies on one, and come with the
+// margin by which the slabs on both sides overlap.
+fn slab_boundaries(segments: &[Segment], n_slabs: usize) -> Vec<(f64, f64)> {
+    let mut xs: Vec<f64> = segments.iter().flat_map(|s| [s.ini.x, s.end.x]).collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    xs.dedup();
+    let mut boundaries: Vec<(f64, f64)> = Vec::new();
+    for k in 1..n_slabs {
+        let i = k * xs.len() / n_slabs;
+        if i == 0 || i >= xs.len() {
+            continue;
+        }
+        let (left, right) = (xs[i - 1], xs[i]);
+        if boundaries.last().is_none_or(|(x, _)| *x < left) {
+            // A crossing exactly on a boundary is inside both overlapping slabs, whatever
+            // the rounding of the clipped endpoints
+            boundaries.push(((left + right) / 2.0, (right - left) / 4.0));
+        }
+    }
+    boundaries
+}
+
+// Piece of the segment between x0 and x1, None if it does not reach that band
+fn clip(segment: &Segment, x0: f64, x1: f64) -> Option<Segment> {
+    let (left, right) = if segment.ini.x <= segment.end.x {
+        (&segment.ini, &segment.end)
+    } else {
+        (&segment.end, &segment.ini)
+    };
+    if right.x < x0 || left.x > x1 {
+        return None;
+    }
+    if left.x == right.x {
+        return Some(segment.clone());
+    }
+    let ini = if left.x < x0 { Point { x: x0, y: segment.interpolate_y(x0) } } else { left.clone() };
+    let end = if right.x > x1 { Point { x: x1, y: segment.interpolate_y(x1) } } else { right.clone() };
+    Some(Segment { ini, end })
+}
+
+fn build_slabs(segments: &[Segment], n_slabs: usize) -> Vec<Slab> {
+    let boundaries = slab_boundaries(segments, n_slabs);
+    let mut slabs = Vec::new();
+    for k in 0..=boundaries.len() {
+        let x0 = if k == 0 { f64::NEG_INFINITY } else { boundaries[k - 1].0 - boundaries[k - 1].1 };
+        let x1 = if k == boundaries.len() { f64::INFINITY } else { boundaries[k].0 + boundaries[k].1 };
+        let mut slab = Slab { pieces: Vec::new(), segments: Vec::new() };
+        for (i, segment) in segments.iter().enumerate() {
+            if let Some(piece) = clip(segment, x0, x1) {
+                slab.pieces.push(piece);
+                slab.segments.push(i);
+            }
+        }
+        slabs.push(slab);
+    }
+    slabs
+}
+
+// Splits the plane in vertical slabs, solves every slab with `slab_solver` on a pool of
+// `n_threads` threads and merges what they found. The slabs only find the pairs: every
+// pair is intersected again on the whole segments, once, so an intersection on a boundary
+// or an overlap across several slabs is reported a single time and exactly.
+pub fn parallel_slab_solver(problem: &mut SweepLineProblem, n_slabs: usize, n_threads: usize, slab_solver: Solver) {
+    let slabs = build_slabs(&problem.segments, n_slabs.max(1));
+    let next_slab = AtomicUsize::new(0);
+    let found: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+    let basic_operations = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..n_threads.max(1) {
+            scope.spawn(|| loop {
+                let k = next_slab.fetch_add(1, Ordering::Relaxed);
+                let Some(slab) = slabs.get(k) else { break };
+                let mut slab_problem = SweepLineProblem::new(slab.pieces.clone());
+                slab_solver(&mut slab_problem);
+                basic_operations.fetch_add(slab_problem.basic_operations as usize, Ordering::Relaxed);
+                let pairs = slab_problem.result_pairs.iter()
+                    .map(|&(a, b)| (slab.segments[a], slab.segments[b]));
+                found.lock().unwrap().extend(pairs);
+            });
+        }
+    });
+
+    let mut intersections = BTreeMap::new();
+    for (i, j) in found.into_inner().unwrap() {
+        let pair = (usize::min(i, j), usize::max(i, j));
+        if pair.0 == pair.1 || intersections.contains_key(&pair) {
+            continue;
+        }
+        if let Some(intersection) = segments_intersection(&problem.segments[pair.0], &problem.segments[pair.1]) {
+            intersections.insert(pair, intersection);
+        }
+    }
+    problem.result = intersections.values().cloned().collect();
+    problem.result_pairs = intersections.keys().copied().collect();
+    problem.basic_operations += (basic_operations.into_inner() + intersections.len()) as i32;
+}
+
+pub fn test_parallel_solver() {
+    let pairs = |problem: &SweepLineProblem| -> Vec<(Segment, (usize, usize))> {
+        problem.result.iter().cloned().zip(problem.result_pairs.iter().cloned()).collect()
+    };
+    // With the naive solver on every slab the merge has to give the naive result exactly,
+    // also for intersections and overlaps on the slab boundaries
+    let mut on_boundaries = SweepLineProblem::new(vec![
+        Segment { ini: Point { x: 0.0, y: 0.0 }, end: Point { x: 4.0, y: 4.0 } },
+        Segment { ini: Point { x: 0.0, y: 4.0 }, end: Point { x: 4.0, y: 0.0 } },
+        Segment { ini: Point { x: 1.0, y: 1.0 }, end: Point { x: 3.0, y: 3.0 } },
+        Segment { ini: Point { x: 2.0, y: -1.0 }, end: Point { x: 2.0, y: 5.0 } },
+        Segment { ini: Point { x: 1.5, y: 0.0 }, end: Point { x: 2.5, y: 0.0 } },
+    ]);
+    for problem in [&mut on_boundaries, &mut create_seeded_example(300, 12), &mut create_short_segments_example(2000, 1000.0, 50.0, 3)] {
+        let mut reference = problem.clone();
+        naive_intersection_solver(&mut reference);
+        for (n_slabs, n_threads) in [(1, 1), (2, 2), (3, 2), (7, 4), (16, 3)] {
+            let mut parallel = problem.clone();
+            parallel_slab_solver(&mut parallel, n_slabs, n_threads, naive_intersection_solver);
+            assert!(same_intersections(&pairs(&parallel), &pairs(&reference)), "{} slabs", n_slabs);
+        }
+    }
+
+    // The sweep on every slab has to give the naive result too, whatever the number of
+    // slabs and threads
+    for problem in [create_seeded_example(300, 7), create_short_segments_example(3000, 2000.0, 60.0, 5)] {
+        let mut reference = problem.clone();
+        naive_intersection_solver(&mut reference);
+        for (n_slabs, n_threads) in [(1, 1), (4, 2), (8, 4), (16, 3)] {
+            let mut parallel = problem.clone();
+            parallel_slab_solver(&mut parallel, n_slabs, n_threads, sweep_line_solver);
+            assert!(same_intersections(&pairs(&parallel), &pairs(&reference)), "{} slabs", n_slabs);
+        }
+    }
+}
+
+// Time of the sweep on slabs for 1 to 16 threads, with as many slabs as threads. Every run
+// has to find what the grid solver finds, so all of them time the same work.
+pub fn benchmark_parallel_solver() {
+    let pairs = |problem: &SweepLineProblem| -> Vec<(Segment, (usize, usize))> {
+        problem.result.iter().cloned().zip(problem.result_pairs.iter().cloned()).collect()
+    };
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for (name, problem) in [
+        ("100000 short segments", create_short_segments_example(100000, 20000.0, 60.0, 1)),
+        ("50000 longer segments", create_short_segments_example(50000, 20000.0, 300.0, 2)),
+    ] {
+        println!("{} on {} cores", name, cores);
+        println!("threads, seconds, speedup, intersections");
+        let mut reference = problem.clone();
+        grid_intersection_solver(&mut reference);
+        let mut single = 0.0;
+        for n_threads in [1, 2, 4, 8, 16] {
+            let mut solved = problem.clone();
+            let start = Instant::now();
+            parallel_slab_solver(&mut solved, n_threads, n_threads, sweep_line_solver);
+            let time = start.elapsed().as_secs_f64();
+            assert!(same_intersections(&pairs(&solved), &pairs(&reference)), "{} threads", n_threads);
+            if n_threads == 1 {
+                single = time;
+            }
+            println!("{}, {:.3}, {:.2}, {}", n_threads, time, single / time, solved.result.len());
+        }
+    }
+}
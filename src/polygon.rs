@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use crate::domain::{Point, Segment, SweepLineProblem, segments_intersection};
+use crate::solvers::sweep_line_solver;
+
+// Closed chain of vertices, the last vertex is connected back to the first one
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Polygon {
+    pub vertices: Vec<Point>,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Point>) -> Polygon {
+        Polygon { vertices }
+    }
+
+    // Edge i goes from vertex i to vertex i + 1
+    pub fn edges(&self) -> Vec<Segment> {
+        let n = self.vertices.len();
+        (0..n).map(|i| Segment {
+            ini: self.vertices[i].clone(),
+            end: self.vertices[(i + 1) % n].clone(),
+        }).collect()
+    }
+
+    fn are_adjacent(&self, i: usize, j: usize) -> bool {
+        let n = self.vertices.len();
+        (i + 1) % n == j || (j + 1) % n == i
+    }
+
+    // Vertex shared by two adjacent edges
+    fn shared_vertex(&self, i: usize, j: usize) -> &Point {
+        let n = self.vertices.len();
+        if (i + 1) % n == j { &self.vertices[j] } else { &self.vertices[i] }
+    }
+
+    // Returns the pairs of edges that intersect together with their intersection.
+    // Two consecutive edges always touch at the vertex they share, that contact is not
+    // reported, but anything else between them (like folding back over each other) is.
+    pub fn self_intersections(&self) -> Vec<(usize, usize, Segment)> {
+        let mut problem = SweepLineProblem::new(self.edges());
+        sweep_line_solver(&mut problem);
+
+        let mut seen = HashSet::new();
+        let mut intersections = Vec::new();
+        for (&(i, j), intersection) in problem.result_pairs.iter().zip(problem.result.iter()) {
+            let (i, j) = (usize::min(i, j), usize::max(i, j));
+            if i == j || !seen.insert((i, j)) {
+                continue;
+            }
+            if self.are_adjacent(i, j) && intersection.ini == intersection.end
+                && intersection.ini == *self.shared_vertex(i, j) {
+                continue;
+            }
+            intersections.push((i, j, intersection.clone()));
+        }
+        intersections
+    }
+
+    pub fn is_simple(&self) -> bool {
+        self.self_intersections().is_empty()
+    }
+}
+
+pub fn test_polygon() {
+    let square = Polygon::new(vec![
+        Point { x: 0.0, y: 0.0 },
+        Point { x: 10.0, y: 0.0 },
+        Point { x: 10.0, y: 10.0 },
+        Point { x: 0.0, y: 10.0 },
+    ]);
+    assert!(square.is_simple());
+
+    let bowtie = Polygon::new(vec![
+        Point { x: 0.0, y: 0.0 },
+        Point { x: 10.0, y: 10.0 },
+        Point { x: 10.0, y: 0.0 },
+        Point { x: 0.0, y: 10.0 },
+    ]);
+    let intersections = bowtie.self_intersections();
+    assert_eq!(intersections.len(), 1);
+    assert_eq!((intersections[0].0, intersections[0].1), (0, 2));
+    assert_eq!(intersections[0].2.ini, Point { x: 5.0, y: 5.0 });
+
+    // The third edge folds back over the second one
+    let spike = Polygon::new(vec![
+        Point { x: 0.0, y: 0.0 },
+        Point { x: 10.0, y: 0.0 },
+        Point { x: 20.0, y: 0.0 },
+        Point { x: 15.0, y: 0.0 },
+        Point { x: 5.0, y: 10.0 },
+    ]);
+    assert!(!spike.is_simple());
+
+    for _ in 0..1000 {
+        let n = 3 + rand::random::<usize>() % 8;
+        let polygon = Polygon::new((0..n).map(|_| Point {
+            x: (rand::random::<u32>() % 100) as f64,
+            y: (rand::random::<u32>() % 100) as f64,
+        }).collect());
+        let edges = polygon.edges();
+        let mut naive_simple = true;
+        for i in 0..n {
+            for j in i + 1..n {
+                if let Some(contact) = segments_intersection(&edges[i], &edges[j]) {
+                    if !(polygon.are_adjacent(i, j) && contact.ini == contact.end
+                        && contact.ini == *polygon.shared_vertex(i, j)) {
+                        naive_simple = false;
+                    }
+                }
+            }
+        }
+        assert_eq!(polygon.is_simple(), naive_simple, "{:?}", polygon.vertices);
+    }
+}
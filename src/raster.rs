@@ -0,0 +1,188 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use crate::camera::Camera;
+use crate::domain::{Point, SweepLineProblem};
+use crate::scene::{build_scene, Scene, Shape, Theme};
+use crate::solvers::sweep_line_solver;
+
+// Samples per pixel side used to antialias the edges of the shapes
+const SUBSAMPLES: usize = 4;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    // Row by row, four bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32, color: [f32; 4]) -> RgbaImage {
+        let pixel = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        RgbaImage { width, height, pixels: pixel.repeat((width * height) as usize) }
+    }
+
+    // Source over blending of the color, weighted by the fraction of the pixel covered
+    fn blend(&mut self, x: u32, y: u32, color: [f32; 4], coverage: f32) {
+        let alpha = color[3].clamp(0.0, 1.0) * coverage;
+        let i = ((y * self.width + x) * 4) as usize;
+        for c in 0..3 {
+            let destination = self.pixels[i + c] as f32 / 255.0;
+            let value = color[c].clamp(0.0, 1.0) * alpha + destination * (1.0 - alpha);
+            self.pixels[i + c] = (value * 255.0).round() as u8;
+        }
+        let destination = self.pixels[i + 3] as f32 / 255.0;
+        self.pixels[i + 3] = ((alpha + destination * (1.0 - alpha)) * 255.0).round() as u8;
+    }
+
+    // Covers the pixels of the box for which `inside` holds at some of the subsamples
+    fn fill(
+        &mut self, x_min: f64, y_min: f64, x_max: f64, y_max: f64, color: [f32; 4],
+        inside: impl Fn(f64, f64) -> bool
+    ) {
+        let x_from = x_min.floor().max(0.0) as u32;
+        let y_from = y_min.floor().max(0.0) as u32;
+        let x_to = (x_max.ceil().max(0.0) as u32).min(self.width);
+        let y_to = (y_max.ceil().max(0.0) as u32).min(self.height);
+        for y in y_from..y_to {
+            for x in x_from..x_to {
+                let mut covered = 0;
+                for sy in 0..SUBSAMPLES {
+                    for sx in 0..SUBSAMPLES {
+                        let px = x as f64 + (sx as f64 + 0.5) / SUBSAMPLES as f64;
+                        let py = y as f64 + (sy as f64 + 0.5) / SUBSAMPLES as f64;
+                        if inside(px, py) {
+                            covered += 1;
+                        }
+                    }
+                }
+                if covered > 0 {
+                    self.blend(x, y, color, covered as f32 / (SUBSAMPLES * SUBSAMPLES) as f32);
+                }
+            }
+        }
+    }
+
+    fn draw_line(&mut self, from: &Point, to: &Point, width: f32, color: [f32; 4]) {
+        let dx = to.x - from.x;
+        let dy = to.y - from.y;
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let half_width = width as f64 / 2.0;
+        // Butt caps, like the lines of the viewer
+        self.fill(
+            from.x.min(to.x) - half_width, from.y.min(to.y) - half_width,
+            from.x.max(to.x) + half_width, from.y.max(to.y) + half_width,
+            color,
+            |px, py| {
+                let along = ((px - from.x) * dx + (py - from.y) * dy) / length;
+                let across = ((px - from.x) * dy - (py - from.y) * dx).abs() / length;
+                (0.0..=length).contains(&along) && across <= half_width
+            }
+        );
+    }
+
+    fn draw_circle(&mut self, center: &Point, radius: f32, color: [f32; 4]) {
+        let radius = radius as f64;
+        self.fill(
+            center.x - radius, center.y - radius, center.x + radius, center.y + radius,
+            color,
+            |px, py| (px - center.x).powi(2) + (py - center.y).powi(2) <= radius * radius
+        );
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Best);
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+        writer.write_image_data(&self.pixels).map_err(|error| error.to_string())
+    }
+
+    pub fn load_png(path: &str) -> Result<RgbaImage, String> {
+        let file = File::open(path).map_err(|error| error.to_string())?;
+        let mut reader = png::Decoder::new(file).read_info().map_err(|error| error.to_string())?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).map_err(|error| error.to_string())?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(format!("{}: only 8 bit RGBA images are supported", path));
+        }
+        pixels.truncate(info.buffer_size());
+        Ok(RgbaImage { width: info.width, height: info.height, pixels })
+    }
+
+    // Number of pixels where some channel differs by more than the tolerance, every pixel
+    // if the sizes do not match
+    pub fn count_different_pixels(&self, other: &RgbaImage, tolerance: u8) -> usize {
+        if self.width != other.width || self.height != other.height {
+            return (self.width * self.height).max(other.width * other.height) as usize;
+        }
+        self.pixels.chunks(4).zip(other.pixels.chunks(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(x, y)| x.abs_diff(*y) > tolerance))
+            .count()
+    }
+}
+
+// Software version of the viewer's draw, for when there is no display
+pub fn render(scene: &Scene) -> RgbaImage {
+    let mut image = RgbaImage::new(scene.width as u32, scene.height as u32, scene.background);
+    let camera = &scene.camera;
+    for shape in scene.shapes.iter() {
+        match shape {
+            Shape::Line { from, to, width, color } => image.draw_line(
+                &camera.world_to_screen(from), &camera.world_to_screen(to), *width, *color
+            ),
+            Shape::Circle { center, radius, color } => image.draw_circle(
+                &camera.world_to_screen(center), *radius, *color
+            ),
+        }
+    }
+    image
+}
+
+// Golden images live next to the problems, in problems/golden/<problem name>.png. A missing
+// or different golden image fails the test. Run it with UPDATE_GOLDEN=1 to write the current
+// renderings as the new golden images instead.
+pub fn test_golden_images() {
+    let channel_tolerance = 8;
+    let max_different_pixels = 100;
+    let update = std::env::var("UPDATE_GOLDEN").map_or(false, |value| value == "1");
+    let mut paths: Vec<_> = std::fs::read_dir("problems").unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "txt"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let mut problem = SweepLineProblem::load(path.to_str().unwrap()).unwrap();
+        sweep_line_solver(&mut problem);
+        let highlight = if problem.segments.is_empty() { None } else { Some((0, 0)) };
+        let scene = build_scene(&problem, 800.0, 600.0, &Camera::default(), 10.0, 1.0, highlight, &Theme::default());
+        let image = render(&scene);
+
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let golden_path = format!("problems/golden/{}.png", name);
+        if update {
+            std::fs::create_dir_all("problems/golden").unwrap();
+            image.save_png(&golden_path).unwrap();
+            println!("{}: updated", golden_path);
+            continue;
+        }
+        assert!(Path::new(&golden_path).exists(), "{} is missing, run with UPDATE_GOLDEN=1 to create it", golden_path);
+        let golden = RgbaImage::load_png(&golden_path).unwrap();
+        let different = image.count_different_pixels(&golden, channel_tolerance);
+        println!("{}: {} different pixels", golden_path, different);
+        if different > max_different_pixels {
+            let actual_path = std::env::temp_dir().join(format!("{}.actual.png", name));
+            image.save_png(actual_path.to_str().unwrap()).unwrap();
+            panic!("{} does not match, the rendering is in {}", golden_path, actual_path.display());
+        }
+    }
+}
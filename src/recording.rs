@@ -0,0 +1,684 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Instant;
+use serde_json::{json, Value};
+use crate::camera::Camera;
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem};
+use crate::raster::{render, RgbaImage};
+use crate::scene::{add_comparison_shapes, add_editing_shapes, add_sweep_shapes, build_scene, Scene, Theme};
+use crate::solvers::{
+    compare_solvers, red_blue_intersection_solver, sweep_line_solver, SolverComparison, SweepLineStepper, SOLVERS
+};
+
+// The viewer is compared with what was recorded at most this often, whatever its frame rate
+const MAX_CAPTURE_FPS: f64 = 30.0;
+
+// How a recorded session is turned into images
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct RecordingOptions {
+    pub fps: f64,
+    // Size of the images, None for the size of the window when recording
+    pub size: Option<(u32, u32)>,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        RecordingOptions { fps: 10.0, size: None }
+    }
+}
+
+impl RecordingOptions {
+    // Frame rate and WIDTHxHEIGHT size as typed on the command line, the defaults for the
+    // ones not given
+    pub fn parse(fps: Option<&str>, size: Option<&str>) -> Result<RecordingOptions, String> {
+        let mut options = RecordingOptions::default();
+        if let Some(fps) = fps {
+            options.fps = fps.parse::<f64>().ok()
+                .filter(|fps| fps.is_finite() && *fps > 0.0)
+                .ok_or_else(|| format!("frame rate must be a positive number, found '{}'", fps))?;
+        }
+        if let Some(size) = size {
+            let parsed = size.split_once('x')
+                .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+                .filter(|(width, height)| *width > 0 && *height > 0);
+            options.size = Some(parsed.ok_or_else(|| format!("size must be WIDTHxHEIGHT in pixels, found '{}'", size))?);
+        }
+        Ok(options)
+    }
+}
+
+// The settings of the viewer that change what it draws, besides the segments
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct View {
+    pub width: f32,
+    pub height: f32,
+    pub camera: Camera,
+    // Minimum distance in pixels between grid lines
+    pub grid_size: f64,
+    pub red_blue: bool,
+    // Steps done while stepping through the sweep, None when it is solved at once
+    pub steps: Option<usize>,
+    // Indices in `SOLVERS` of the two solvers being compared
+    pub compared_solvers: Option<(usize, usize)>,
+}
+
+// Everything a recording follows. The mouse, hover highlights and overlay text are left out.
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct ViewState {
+    pub view: View,
+    pub segments: Vec<Segment>,
+    pub colors: Vec<SegmentColor>,
+    pub selection: Vec<usize>,
+}
+
+// A change of the viewer, recorded instead of the scenes it draws
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum SessionEvent {
+    View(View),
+    // New number of segments and the segments below it that are new or changed
+    Segments { count: usize, changed: Vec<(usize, Segment, SegmentColor)> },
+    Selection(Vec<usize>),
+}
+
+// The state when recording started and the changes after it, with the seconds since the start
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Session {
+    pub theme: Theme,
+    pub initial: ViewState,
+    pub events: Vec<(f64, SessionEvent)>,
+    pub duration: f64,
+}
+
+impl ViewState {
+    pub fn new(view: &View, problem: &SweepLineProblem, selection: &[usize]) -> ViewState {
+        ViewState {
+            view: view.clone(),
+            segments: problem.segments.clone(),
+            colors: problem.segment_info.iter().map(|info| info.color).collect(),
+            selection: selection.to_vec(),
+        }
+    }
+
+    pub fn apply(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::View(view) => self.view = view.clone(),
+            SessionEvent::Segments { count, changed } => {
+                self.segments.truncate(*count);
+                self.colors.truncate(*count);
+                for (i, segment, color) in changed {
+                    if *i < self.segments.len() {
+                        self.segments[*i] = segment.clone();
+                        self.colors[*i] = *color;
+                    } else {
+                        self.segments.push(segment.clone());
+                        self.colors.push(*color);
+                    }
+                }
+            }
+            SessionEvent::Selection(selection) => self.selection = selection.clone(),
+        }
+    }
+
+    fn problem(&self) -> SweepLineProblem {
+        let mut problem = SweepLineProblem::new(Vec::new());
+        for (segment, color) in self.segments.iter().zip(self.colors.iter()) {
+            problem.add_segment(segment.clone(), SegmentInfo::colored(*color));
+        }
+        problem
+    }
+}
+
+// Changes of the viewer since recording started. Only what differs from the last capture
+// is kept, so a still view costs nothing however long it is recorded.
+pub struct Recording {
+    start: Instant,
+    last_capture: Option<f64>,
+    theme: Theme,
+    initial: Option<ViewState>,
+    current: Option<ViewState>,
+    events: Vec<(f64, SessionEvent)>,
+}
+
+impl Recording {
+    pub fn new(theme: &Theme) -> Recording {
+        Recording {
+            start: Instant::now(),
+            last_capture: None,
+            theme: theme.clone(),
+            initial: None,
+            current: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn capture(&mut self, view: &View, problem: &SweepLineProblem, selection: &[usize]) {
+        let time = self.start.elapsed().as_secs_f64();
+        if self.last_capture.is_none_or(|last| time - last >= 1.0 / MAX_CAPTURE_FPS) {
+            self.capture_at(time, view, problem, selection);
+        }
+    }
+
+    pub fn capture_at(&mut self, time: f64, view: &View, problem: &SweepLineProblem, selection: &[usize]) {
+        self.last_capture = Some(time);
+        let Some(current) = &mut self.current else {
+            self.initial = Some(ViewState::new(view, problem, selection));
+            self.current = self.initial.clone();
+            return;
+        };
+        let mut events = Vec::new();
+        if current.view != *view {
+            events.push(SessionEvent::View(view.clone()));
+        }
+        let changed: Vec<(usize, Segment, SegmentColor)> = problem.segments.iter()
+            .zip(problem.segment_info.iter())
+            .enumerate()
+            .filter(|(i, (segment, info))| {
+                current.segments.get(*i) != Some(*segment) || current.colors.get(*i) != Some(&info.color)
+            })
+            .map(|(i, (segment, info))| (i, segment.clone(), info.color))
+            .collect();
+        if !changed.is_empty() || problem.segments.len() != current.segments.len() {
+            events.push(SessionEvent::Segments { count: problem.segments.len(), changed });
+        }
+        if current.selection != selection {
+            events.push(SessionEvent::Selection(selection.to_vec()));
+        }
+        for event in events {
+            current.apply(&event);
+            self.events.push((time, event));
+        }
+    }
+
+    // The session recorded so far, None if nothing was captured
+    pub fn finish(self) -> Option<Session> {
+        let duration = self.start.elapsed().as_secs_f64().max(self.last_capture.unwrap_or(0.0));
+        Some(Session { theme: self.theme, initial: self.initial?, events: self.events, duration })
+    }
+}
+
+// Plays a session back, solving again whenever the segments or the mode change
+pub struct Replay<'a> {
+    session: &'a Session,
+    next_event: usize,
+    state: ViewState,
+    problem: SweepLineProblem,
+    stepper: Option<SweepLineStepper>,
+    done_steps: usize,
+    comparison: Option<SolverComparison>,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(session: &'a Session) -> Replay<'a> {
+        let mut replay = Replay {
+            session,
+            next_event: 0,
+            state: session.initial.clone(),
+            problem: session.initial.problem(),
+            stepper: None,
+            done_steps: 0,
+            comparison: None,
+        };
+        replay.solve(true);
+        replay
+    }
+
+    fn solve(&mut self, segments_changed: bool) {
+        if segments_changed {
+            self.problem = self.state.problem();
+        }
+        let view = &self.state.view;
+        if let Some(steps) = view.steps {
+            // Going forward continues the same sweep, anything else starts it again
+            if segments_changed || self.stepper.is_none() || steps < self.done_steps {
+                self.stepper = Some(SweepLineStepper::new(&mut self.problem));
+                self.done_steps = 0;
+            }
+            let stepper = self.stepper.as_mut().unwrap();
+            while self.done_steps < steps && stepper.step(&mut self.problem) {
+                self.done_steps += 1;
+            }
+            self.comparison = None;
+            return;
+        }
+        self.stepper = None;
+        self.comparison = view.compared_solvers.map(|(a, b)| compare_solvers(&self.problem, a, b));
+        if self.comparison.is_some() {
+            self.problem.result.clear();
+            self.problem.result_pairs.clear();
+        } else if view.red_blue {
+            red_blue_intersection_solver(&mut self.problem);
+        } else {
+            sweep_line_solver(&mut self.problem);
+        }
+    }
+
+    // The scene at `time` seconds, which must not be earlier than the last one asked for
+    pub fn scene_at(&mut self, time: f64) -> Scene {
+        let (mut segments_changed, mut mode_changed) = (false, false);
+        while let Some((event_time, event)) = self.session.events.get(self.next_event) {
+            if *event_time > time {
+                break;
+            }
+            match event {
+                SessionEvent::View(view) => {
+                    let view_before = &self.state.view;
+                    mode_changed |= view.red_blue != view_before.red_blue
+                        || view.steps != view_before.steps
+                        || view.compared_solvers != view_before.compared_solvers;
+                }
+                SessionEvent::Segments { .. } => segments_changed = true,
+                SessionEvent::Selection(_) => (),
+            }
+            self.state.apply(event);
+            self.next_event += 1;
+        }
+        if segments_changed || mode_changed {
+            self.solve(segments_changed);
+        }
+
+        let view = &self.state.view;
+        let mut scene = build_scene(
+            &self.problem, view.width, view.height, &view.camera, view.camera.grid_size(view.grid_size),
+            1.0, None, &self.session.theme
+        );
+        if let Some(stepper) = &self.stepper {
+            add_sweep_shapes(&mut scene, &self.problem, stepper);
+        }
+        let selection: Vec<usize> = self.state.selection.iter().copied()
+            .filter(|&i| i < self.problem.segments.len())
+            .collect();
+        add_editing_shapes(&mut scene, &self.problem, &selection, None, None);
+        if let Some(comparison) = &self.comparison {
+            add_comparison_shapes(&mut scene, comparison, 1.0);
+        }
+        scene
+    }
+}
+
+fn point_to_json(p: &Point) -> Value {
+    json!([p.x, p.y])
+}
+
+fn point_from_json(value: &Value) -> Result<Point, String> {
+    match (value[0].as_f64(), value[1].as_f64()) {
+        (Some(x), Some(y)) => Ok(Point { x, y }),
+        _ => Err(format!("expected a point, found {}", value)),
+    }
+}
+
+fn number_from_json(value: &Value, name: &str) -> Result<f64, String> {
+    value[name].as_f64().ok_or_else(|| format!("expected a number for {}, found {}", name, value[name]))
+}
+
+// Sizes of the view, a zero, negative or infinite one can not be drawn
+fn size_from_json(value: &Value, name: &str) -> Result<f64, String> {
+    let size = number_from_json(value, name)?;
+    if !(size.is_finite() && size > 0.0) {
+        return Err(format!("{} must be a positive number, found {}", name, size));
+    }
+    Ok(size)
+}
+
+fn index_from_json(value: &Value) -> Result<usize, String> {
+    value.as_u64().map(|index| index as usize).ok_or_else(|| format!("expected an index, found {}", value))
+}
+
+fn view_to_json(view: &View) -> Value {
+    json!({
+        "width": view.width,
+        "height": view.height,
+        "camera": { "origin": point_to_json(&view.camera.origin), "scale": view.camera.scale },
+        "grid_size": view.grid_size,
+        "red_blue": view.red_blue,
+        "steps": view.steps,
+        // Solvers by name, their indices change when solvers are added
+        "compare": view.compared_solvers.map(|(a, b)| [SOLVERS[a].0, SOLVERS[b].0]),
+    })
+}
+
+fn view_from_json(value: &Value) -> Result<View, String> {
+    let solver = |name: &Value| {
+        SOLVERS.iter().position(|(solver, _)| Some(*solver) == name.as_str())
+            .ok_or_else(|| format!("unknown solver {}", name))
+    };
+    Ok(View {
+        width: size_from_json(value, "width")? as f32,
+        height: size_from_json(value, "height")? as f32,
+        camera: Camera {
+            origin: point_from_json(&value["camera"]["origin"])?,
+            scale: size_from_json(&value["camera"], "scale")?,
+        },
+        grid_size: size_from_json(value, "grid_size")?,
+        red_blue: value["red_blue"].as_bool().ok_or_else(|| "expected red_blue".to_string())?,
+        steps: if value["steps"].is_null() { None } else { Some(index_from_json(&value["steps"])?) },
+        compared_solvers: if value["compare"].is_null() {
+            None
+        } else {
+            Some((solver(&value["compare"][0])?, solver(&value["compare"][1])?))
+        },
+    })
+}
+
+// A segment as [x1, y1, x2, y2, color]
+fn segment_to_json(segment: &Segment, color: SegmentColor) -> Value {
+    json!([segment.ini.x, segment.ini.y, segment.end.x, segment.end.y, color.name()])
+}
+
+fn segment_from_json(value: &Value) -> Result<(Segment, SegmentColor), String> {
+    let coordinate = |k: usize| value[k].as_f64().ok_or_else(|| format!("expected a segment, found {}", value));
+    let segment = Segment {
+        ini: Point { x: coordinate(0)?, y: coordinate(1)? },
+        end: Point { x: coordinate(2)?, y: coordinate(3)? },
+    };
+    let color = value[4].as_str().and_then(SegmentColor::parse)
+        .ok_or_else(|| format!("unknown color {}", value[4]))?;
+    Ok((segment, color))
+}
+
+fn indices_from_json(value: &Value) -> Result<Vec<usize>, String> {
+    value.as_array().ok_or_else(|| format!("expected indices, found {}", value))?
+        .iter().map(index_from_json).collect()
+}
+
+pub fn event_to_json(time: f64, event: &SessionEvent) -> Value {
+    match event {
+        SessionEvent::View(view) => json!({ "time": time, "kind": "view", "view": view_to_json(view) }),
+        SessionEvent::Segments { count, changed } => {
+            let changed: Vec<Value> = changed.iter()
+                .map(|(i, segment, color)| json!({ "index": i, "segment": segment_to_json(segment, *color) }))
+                .collect();
+            json!({ "time": time, "kind": "segments", "count": count, "changed": changed })
+        }
+        SessionEvent::Selection(selection) => json!({ "time": time, "kind": "selection", "selection": selection }),
+    }
+}
+
+pub fn event_from_json(value: &Value) -> Result<(f64, SessionEvent), String> {
+    let event = match value["kind"].as_str() {
+        Some("view") => SessionEvent::View(view_from_json(&value["view"])?),
+        Some("segments") => SessionEvent::Segments {
+            count: index_from_json(&value["count"])?,
+            changed: value["changed"].as_array()
+                .ok_or_else(|| "segments event without changes".to_string())?
+                .iter()
+                .map(|change| {
+                    let (segment, color) = segment_from_json(&change["segment"])?;
+                    Ok((index_from_json(&change["index"])?, segment, color))
+                })
+                .collect::<Result<Vec<_>, String>>()?,
+        },
+        Some("selection") => SessionEvent::Selection(indices_from_json(&value["selection"])?),
+        _ => return Err(format!("unknown event kind {}", value["kind"])),
+    };
+    Ok((number_from_json(value, "time")?, event))
+}
+
+// A session is written as JSON Lines: the theme and the starting state, one event per line
+// and the duration at the end, so it can be rendered again
+pub fn save_session(session: &Session, path: &str) -> Result<(), String> {
+    let initial = &session.initial;
+    let segments: Vec<Value> = initial.segments.iter().zip(initial.colors.iter())
+        .map(|(segment, color)| segment_to_json(segment, *color))
+        .collect();
+    let theme = serde_json::to_value(&session.theme).map_err(|error| error.to_string())?;
+    let mut text = json!({
+        "kind": "start",
+        "theme": theme,
+        "view": view_to_json(&initial.view),
+        "segments": segments,
+        "selection": initial.selection,
+    }).to_string();
+    text.push('\n');
+    for (time, event) in session.events.iter() {
+        text.push_str(&event_to_json(*time, event).to_string());
+        text.push('\n');
+    }
+    text.push_str(&json!({ "kind": "stop", "time": session.duration }).to_string());
+    text.push('\n');
+    std::fs::write(path, text).map_err(|error| format!("{}: {}", path, error))
+}
+
+pub fn load_session(path: &str) -> Result<Session, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let mut lines = text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<Value>(line)
+                .map(|value| (i + 1, value))
+                .map_err(|error| format!("{}: line {}: {}", path, i + 1, error))
+        });
+    let (_, start) = lines.next().ok_or_else(|| format!("{}: empty session", path))??;
+    let read_start = || -> Result<Session, String> {
+        if start["kind"] != "start" {
+            return Err(format!("expected the start of the session, found {}", start["kind"]));
+        }
+        let theme: Theme = serde_json::from_value(start["theme"].clone()).map_err(|error| error.to_string())?;
+        let (segments, colors) = start["segments"].as_array()
+            .ok_or_else(|| "start without segments".to_string())?
+            .iter()
+            .map(segment_from_json)
+            .collect::<Result<Vec<_>, String>>()?
+            .into_iter()
+            .unzip();
+        let initial = ViewState {
+            view: view_from_json(&start["view"])?,
+            segments,
+            colors,
+            selection: indices_from_json(&start["selection"])?,
+        };
+        Ok(Session { theme, initial, events: Vec::new(), duration: 0.0 })
+    };
+    let mut session = read_start().map_err(|error| format!("{}: line 1: {}", path, error))?;
+    for line in lines {
+        let (line_number, value) = line?;
+        if value["kind"] == "stop" {
+            session.duration = number_from_json(&value, "time")
+                .map_err(|error| format!("{}: line {}: {}", path, line_number, error))?;
+            break;
+        }
+        let event = event_from_json(&value).map_err(|error| format!("{}: line {}: {}", path, line_number, error))?;
+        session.duration = session.duration.max(event.0);
+        session.events.push(event);
+    }
+    Ok(session)
+}
+
+// The same scene at another size, zoomed so that what was visible stays visible and centered
+pub fn fit_scene(scene: &Scene, width: u32, height: u32) -> Scene {
+    let factor = f64::min(width as f64 / scene.width as f64, height as f64 / scene.height as f64);
+    let center = scene.camera.screen_to_world(&Point { x: scene.width as f64 / 2.0, y: scene.height as f64 / 2.0 });
+    let scale = scene.camera.scale * factor;
+    let camera = Camera {
+        origin: Point { x: center.x - width as f64 / 2.0 / scale, y: center.y - height as f64 / 2.0 / scale },
+        scale,
+    };
+    Scene { width: width as f32, height: height as f32, camera, ..scene.clone() }
+}
+
+// Number of images of the session at the given frame rate, the first one at the start
+pub fn frame_count(session: &Session, fps: f64) -> usize {
+    (session.duration * fps).floor() as usize + 1
+}
+
+// Replays the session and renders it at the frame rate of the options, handing every image
+// to `write` as soon as it is drawn
+pub fn render_session(
+    session: &Session,
+    options: &RecordingOptions,
+    mut write: impl FnMut(usize, &RgbaImage) -> Result<(), String>,
+) -> Result<usize, String> {
+    let mut replay = Replay::new(session);
+    let n_frames = frame_count(session, options.fps);
+    for k in 0..n_frames {
+        let scene = replay.scene_at(k as f64 / options.fps);
+        let image = match options.size {
+            Some((width, height)) => render(&fit_scene(&scene, width, height)),
+            None => render(&scene),
+        };
+        write(k, &image)?;
+    }
+    Ok(n_frames)
+}
+
+// An animated GIF if the path ends in .gif, numbered PNG frames, frame_00000.png and on, in
+// the directory at the path otherwise
+pub fn export_session(session: &Session, options: &RecordingOptions, path: &str) -> Result<usize, String> {
+    if !path.to_ascii_lowercase().ends_with(".gif") {
+        std::fs::create_dir_all(path).map_err(|error| format!("{}: {}", path, error))?;
+        return render_session(session, options, |k, image| image.save_png(&format!("{}/frame_{:05}.png", path, k)));
+    }
+    let mut encoder = None;
+    // Hundredths of a second, the unit of GIF delays
+    let delay = (100.0 / options.fps).round().max(1.0) as u16;
+    render_session(session, options, |_, image| {
+        let (width, height) = (image.width as u16, image.height as u16);
+        if encoder.is_none() {
+            let file = File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+            let mut new_encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[])
+                .map_err(|error| format!("{}: {}", path, error))?;
+            new_encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| format!("{}: {}", path, error))?;
+            encoder = Some(new_encoder);
+        }
+        let mut pixels = image.pixels.clone();
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        frame.delay = delay;
+        encoder.as_mut().unwrap().write_frame(&frame).map_err(|error| format!("{}: {}", path, error))
+    })
+}
+
+pub fn test_recording() {
+    let directory = std::env::temp_dir().join("sweep_line_recording");
+    std::fs::create_dir_all(&directory).unwrap();
+    let directory = directory.to_str().unwrap().to_string();
+
+    // A step-through of the sweep at eight steps a second, then a segment dragged and the
+    // view panned and switched to red-blue mode
+    let mut problem = SweepLineProblem::load("problems/sweep_line_problem_0.txt").unwrap();
+    let mut view = View {
+        width: 200.0,
+        height: 150.0,
+        camera: Camera::default(),
+        grid_size: 10.0,
+        red_blue: false,
+        steps: Some(0),
+        compared_solvers: None,
+    };
+    let mut recording = Recording::new(&Theme::default());
+    let mut stepped = problem.clone();
+    let mut stepper = SweepLineStepper::new(&mut stepped);
+    let mut time = 0.0;
+    loop {
+        recording.capture_at(time, &view, &problem, &[]);
+        time += 1.0 / 8.0;
+        if !stepper.step(&mut stepped) {
+            break;
+        }
+        view.steps = view.steps.map(|steps| steps + 1);
+    }
+    let n_steps = view.steps.unwrap();
+    // Nothing changes, nothing is recorded
+    let n_events = recording.events.len();
+    for _ in 0..100 {
+        recording.capture_at(time, &view, &problem, &[]);
+    }
+    assert_eq!(recording.events.len(), n_events);
+    assert_eq!(n_events, n_steps);
+
+    view.steps = None;
+    for k in 0..4 {
+        problem.segments[1].end.x += 10.0;
+        view.camera.pan(5.0, 0.0);
+        recording.capture_at(time + k as f64 / 8.0, &view, &problem, &[1]);
+    }
+    time += 0.5;
+    view.red_blue = true;
+    problem.segment_info[0].color = SegmentColor::Blue;
+    problem.add_segment(problem.segments[0].clone(), SegmentInfo::default());
+    recording.capture_at(time, &view, &problem, &[]);
+    let mut session = recording.finish().unwrap();
+    session.duration = time;
+    // Only the dragged segment is stored again, not the whole problem
+    for (_, event) in session.events.iter() {
+        if let SessionEvent::Segments { changed, .. } = event {
+            assert!(changed.len() <= 2);
+        }
+    }
+
+    let path = format!("{}/session.jsonl", directory);
+    save_session(&session, &path).unwrap();
+    let loaded = load_session(&path).unwrap();
+    assert!(loaded == session);
+
+    // A hand-edited view that can not be drawn is rejected instead of hanging the render
+    let text = std::fs::read_to_string(&path).unwrap();
+    let (start, rest) = text.split_once('\n').unwrap();
+    let edited_path = format!("{}/edited.jsonl", directory);
+    for (field, value) in [("grid_size", json!(0.0)), ("grid_size", json!(-10.0)), ("width", json!(0.0)),
+                           ("height", json!(-1.0)), ("scale", json!(0.0))] {
+        let mut start: Value = serde_json::from_str(start).unwrap();
+        if field == "scale" {
+            start["view"]["camera"][field] = value;
+        } else {
+            start["view"][field] = value;
+        }
+        std::fs::write(&edited_path, format!("{}\n{}", start, rest)).unwrap();
+        assert!(load_session(&edited_path).is_err_and(|error| error.contains(field)), "{}", field);
+    }
+
+    // The replay draws what the viewer drew at every moment
+    let mut replay = Replay::new(&session);
+    let mut expected_problem = SweepLineProblem::load("problems/sweep_line_problem_0.txt").unwrap();
+    let mut expected_stepper = SweepLineStepper::new(&mut expected_problem);
+    for step in 0..=n_steps {
+        let scene = replay.scene_at(step as f64 / 8.0);
+        let mut expected = build_scene(&expected_problem, 200.0, 150.0, &Camera::default(), 10.0, 1.0, None, &Theme::default());
+        add_sweep_shapes(&mut expected, &expected_problem, &expected_stepper);
+        assert!(scene == expected, "step {}", step);
+        expected_stepper.step(&mut expected_problem);
+    }
+    let scene = replay.scene_at(time);
+    let mut expected = problem.clone();
+    red_blue_intersection_solver(&mut expected);
+    let expected_scene = build_scene(&expected, 200.0, 150.0, &view.camera, view.camera.grid_size(10.0), 1.0, None, &Theme::default());
+    assert!(scene == expected_scene);
+
+    let options = RecordingOptions { fps: 5.0, size: Some((100, 80)) };
+    let gif_path = format!("{}/session.gif", directory);
+    let n_frames = export_session(&session, &options, &gif_path).unwrap();
+    assert_eq!(n_frames, frame_count(&session, 5.0));
+    let gif = std::fs::read(&gif_path).unwrap();
+    assert!(gif.starts_with(b"GIF89a"));
+    assert_eq!(u16::from_le_bytes([gif[6], gif[7]]), 100);
+
+    let png_directory = format!("{}/frames", directory);
+    let _ = std::fs::remove_dir_all(&png_directory);
+    export_session(&session, &RecordingOptions::default(), &png_directory).unwrap();
+    let image = RgbaImage::load_png(&format!("{}/frame_00000.png", png_directory)).unwrap();
+    assert_eq!((image.width, image.height), (200, 150));
+    assert_eq!(std::fs::read_dir(&png_directory).unwrap().count(), frame_count(&session, 10.0));
+
+    // Fitting keeps the center of the view in the center
+    let fitted = fit_scene(&Replay::new(&session).scene_at(0.0), 400, 400);
+    let center = fitted.camera.screen_to_world(&Point { x: 200.0, y: 200.0 });
+    assert!((center.x - 100.0).abs() < 1e-9 && (center.y - 75.0).abs() < 1e-9);
+    assert!(load_session(&format!("{}/missing.jsonl", directory)).is_err());
+    std::fs::write(&path, "{\"kind\": \"start\"}\n").unwrap();
+    assert!(load_session(&path).is_err_and(|error| error.contains("line 1")));
+
+    assert_eq!(RecordingOptions::parse(Some("15"), Some("640x480")).unwrap(), RecordingOptions { fps: 15.0, size: Some((640, 480)) });
+    assert_eq!(RecordingOptions::parse(None, None).unwrap(), RecordingOptions::default());
+    for (fps, size) in [(Some("fast"), None), (Some("0"), None), (Some("-5"), None), (Some("inf"), None),
+                        (None, Some("640")), (None, Some("0x480")), (None, Some("640xabc"))] {
+        assert!(RecordingOptions::parse(fps, size).is_err(), "{:?} {:?}", fps, size);
+    }
+}
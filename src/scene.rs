@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use crate::camera::Camera;
+use crate::domain::{Point, SegmentColor, SweepLineProblem};
+use crate::solvers::{EventType, SolverComparison, SweepLineStepper};
+
+// What the viewer draws in a frame, independent of the backend that draws it. Points are in
+// world coordinates, widths and radii in pixels.
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum Shape {
+    Line { from: Point, to: Point, width: f32, color: [f32; 4] },
+    Circle { center: Point, radius: f32, color: [f32; 4] },
+}
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Scene {
+    pub width: f32,
+    pub height: f32,
+    pub background: [f32; 4],
+    // Maps the points of the shapes to pixels
+    pub camera: Camera,
+    // Drawn in order, later shapes on top
+    pub shapes: Vec<Shape>,
+}
+
+pub const BACKGROUND_COLOR: [f32; 4] = [0.1, 0.2, 0.3, 1.0];
+pub const GRID_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.2];
+pub const RED_SEGMENT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const BLUE_SEGMENT_COLOR: [f32; 4] = [0.4, 0.7, 1.0, 1.0];
+pub const INTERSECTION_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+pub const HIGHLIGHT_COLOR: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+pub const SWEEP_LINE_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 0.8];
+// Status segments go from the first color to the second one in the treap order
+pub const STATUS_FIRST_COLOR: [f32; 3] = [0.2, 1.0, 0.4];
+pub const STATUS_LAST_COLOR: [f32; 3] = [1.0, 1.0, 0.2];
+pub const START_EVENT_COLOR: [f32; 4] = [0.2, 1.0, 0.4, 1.0];
+pub const CROSS_EVENT_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+pub const END_EVENT_COLOR: [f32; 4] = [1.0, 0.5, 0.0, 1.0];
+pub const NEW_INTERSECTION_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+pub const FOUND_BY_BOTH_COLOR: [f32; 3] = [0.2, 1.0, 0.2];
+pub const ONLY_A_COLOR: [f32; 3] = [1.0, 0.6, 0.0];
+pub const ONLY_B_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+pub const SELECTION_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0];
+pub const SELECTION_RECTANGLE_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 0.6];
+pub const POLYLINE_PREVIEW_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.5];
+
+// Colors and sizes of the grid, the segments and the intersections, in pixels
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub background: [f32; 4],
+    pub grid: [f32; 4],
+    pub red_segment: [f32; 4],
+    pub blue_segment: [f32; 4],
+    // The alpha of the intersections is the one given to `build_scene`
+    pub intersection: [f32; 3],
+    pub highlight: [f32; 4],
+    pub grid_width: f32,
+    pub segment_width: f32,
+    pub endpoint_radius: f32,
+    pub intersection_width: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: BACKGROUND_COLOR,
+            grid: GRID_COLOR,
+            red_segment: RED_SEGMENT_COLOR,
+            blue_segment: BLUE_SEGMENT_COLOR,
+            intersection: INTERSECTION_COLOR,
+            highlight: HIGHLIGHT_COLOR,
+            grid_width: 1.0,
+            segment_width: 2.0,
+            endpoint_radius: 2.0,
+            intersection_width: 5.0,
+        }
+    }
+}
+
+impl Scene {
+    // Visible world region as (x min, y min, x max, y max)
+    pub fn visible(&self) -> (f64, f64, f64, f64) {
+        self.camera.visible(self.width as f64, self.height as f64)
+    }
+}
+
+// Grid, segments with their endpoints, intersections with the given alpha and the
+// highlighted endpoint, as (segment index, 0 for ini or 1 for end). The grid lines are
+// `grid_size` world units apart, and the theme gives the colors and sizes.
+pub fn build_scene(
+    problem: &SweepLineProblem,
+    width: f32,
+    height: f32,
+    camera: &Camera,
+    grid_size: f64,
+    intersection_alpha: f32,
+    highlight_point_index: Option<(usize, usize)>,
+    theme: &Theme,
+) -> Scene {
+    let mut shapes = Vec::new();
+    let (x_min, y_min, x_max, y_max) = camera.visible(width as f64, height as f64);
+
+    let mut i = (x_min / grid_size).ceil();
+    while i * grid_size < x_max {
+        let x = i * grid_size;
+        shapes.push(Shape::Line {
+            from: Point { x, y: y_min },
+            to: Point { x, y: y_max },
+            width: theme.grid_width,
+            color: theme.grid,
+        });
+        i += 1.0;
+    }
+    let mut i = (y_min / grid_size).ceil();
+    while i * grid_size < y_max {
+        let y = i * grid_size;
+        shapes.push(Shape::Line {
+            from: Point { x: x_min, y },
+            to: Point { x: x_max, y },
+            width: theme.grid_width,
+            color: theme.grid,
+        });
+        i += 1.0;
+    }
+
+    for (segment, info) in problem.segments.iter().zip(problem.segment_info.iter()) {
+        let color = match info.color {
+            SegmentColor::Red => theme.red_segment,
+            SegmentColor::Blue => theme.blue_segment,
+        };
+        let radius = theme.endpoint_radius;
+        shapes.push(Shape::Line {
+            from: segment.ini.clone(), to: segment.end.clone(), width: theme.segment_width, color
+        });
+        shapes.push(Shape::Circle { center: segment.ini.clone(), radius, color });
+        shapes.push(Shape::Circle { center: segment.end.clone(), radius, color });
+    }
+
+    let [r, g, b] = theme.intersection;
+    let color = [r, g, b, intersection_alpha];
+    let radius = theme.intersection_width;
+    for intersection in problem.result.iter() {
+        shapes.push(Shape::Line {
+            from: intersection.ini.clone(), to: intersection.end.clone(), width: theme.intersection_width, color
+        });
+        shapes.push(Shape::Circle { center: intersection.ini.clone(), radius, color });
+        shapes.push(Shape::Circle { center: intersection.end.clone(), radius, color });
+    }
+
+    if let Some((i, end)) = highlight_point_index {
+        let segment = &problem.segments[i];
+        let point = if end == 0 { &segment.ini } else { &segment.end };
+        shapes.push(Shape::Circle { center: point.clone(), radius: 5.0, color: theme.highlight });
+    }
+
+    Scene { width, height, background: theme.background, camera: camera.clone(), shapes }
+}
+
+// State of a stepped sweep on top of a scene: the status segments in order, the sweep line,
+// the pending events and the intersection found by the last step
+pub fn add_sweep_shapes(scene: &mut Scene, problem: &SweepLineProblem, stepper: &SweepLineStepper) {
+    let status = stepper.status();
+    for (k, &i) in status.iter().enumerate() {
+        let t = if status.len() > 1 { k as f32 / (status.len() - 1) as f32 } else { 0.0 };
+        let color = [0, 1, 2].map(|c| STATUS_FIRST_COLOR[c] + (STATUS_LAST_COLOR[c] - STATUS_FIRST_COLOR[c]) * t);
+        let color = [color[0], color[1], color[2], 1.0];
+        let segment = &problem.segments[i];
+        scene.shapes.push(Shape::Line { from: segment.ini.clone(), to: segment.end.clone(), width: 3.0, color });
+        if let Some(x) = stepper.sweep_x {
+            let center = Point { x, y: segment.interpolate_y(x) };
+            scene.shapes.push(Shape::Circle { center, radius: 4.0, color });
+        }
+    }
+
+    if let Some(x) = stepper.sweep_x {
+        let (_, y_min, _, y_max) = scene.visible();
+        scene.shapes.push(Shape::Line {
+            from: Point { x, y: y_min },
+            to: Point { x, y: y_max },
+            width: 1.0,
+            color: SWEEP_LINE_COLOR,
+        });
+    }
+
+    for (point, event_type) in stepper.pending_events() {
+        let color = match event_type {
+            EventType::Start => START_EVENT_COLOR,
+            EventType::Cross => CROSS_EVENT_COLOR,
+            EventType::End => END_EVENT_COLOR,
+        };
+        scene.shapes.push(Shape::Circle { center: point, radius: 3.0, color });
+    }
+
+    if let Some(k) = stepper.last_intersection {
+        let intersection = &problem.result[k];
+        for center in [&intersection.ini, &intersection.end] {
+            scene.shapes.push(Shape::Circle { center: center.clone(), radius: 9.0, color: NEW_INTERSECTION_COLOR });
+        }
+    }
+}
+
+// Editing state on top of a scene: the selected segments, the rectangle being dragged to
+// select and the next segment of the polyline being drawn
+pub fn add_editing_shapes(
+    scene: &mut Scene,
+    problem: &SweepLineProblem,
+    selection: &[usize],
+    rectangle: Option<(Point, Point)>,
+    polyline_preview: Option<(Point, Point)>,
+) {
+    for &i in selection {
+        let segment = &problem.segments[i];
+        scene.shapes.push(Shape::Line {
+            from: segment.ini.clone(), to: segment.end.clone(), width: 3.0, color: SELECTION_COLOR
+        });
+    }
+    if let Some((a, b)) = rectangle {
+        let corners = [
+            a.clone(), Point { x: b.x, y: a.y }, b.clone(), Point { x: a.x, y: b.y }, a,
+        ];
+        for pair in corners.windows(2) {
+            scene.shapes.push(Shape::Line {
+                from: pair[0].clone(), to: pair[1].clone(), width: 1.0, color: SELECTION_RECTANGLE_COLOR
+            });
+        }
+    }
+    if let Some((from, to)) = polyline_preview {
+        scene.shapes.push(Shape::Line { from, to, width: 2.0, color: POLYLINE_PREVIEW_COLOR });
+    }
+}
+
+// Intersections of a solver comparison, colored by which of the two solvers found them
+pub fn add_comparison_shapes(scene: &mut Scene, comparison: &SolverComparison, alpha: f32) {
+    for (intersections, [r, g, b]) in [
+        (&comparison.both, FOUND_BY_BOTH_COLOR),
+        (&comparison.only_a, ONLY_A_COLOR),
+        (&comparison.only_b, ONLY_B_COLOR),
+    ] {
+        let color = [r, g, b, alpha];
+        for intersection in intersections.iter() {
+            scene.shapes.push(Shape::Line {
+                from: intersection.ini.clone(), to: intersection.end.clone(), width: 5.0, color
+            });
+            scene.shapes.push(Shape::Circle { center: intersection.ini.clone(), radius: 5.0, color });
+            scene.shapes.push(Shape::Circle { center: intersection.end.clone(), radius: 5.0, color });
+        }
+    }
+}
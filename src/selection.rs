@@ -0,0 +1,117 @@
+use crate::domain::{distance, Point, Segment, SweepLineProblem};
+use crate::example_generator::create_seeded_example;
+use crate::history::{remove_command, EditCommand, EditHistory};
+
+// Distance from a point to the closest point of a segment
+pub fn distance_to_segment(p: &Point, s: &Segment) -> f64 {
+    let dx = s.end.x - s.ini.x;
+    let dy = s.end.y - s.ini.y;
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return distance(p, &s.ini);
+    }
+    let t = (((p.x - s.ini.x) * dx + (p.y - s.ini.y) * dy) / length_squared).clamp(0.0, 1.0);
+    distance(p, &Point { x: s.ini.x + t * dx, y: s.ini.y + t * dy })
+}
+
+// Closest segment within `radius` of the point, the last drawn one on ties
+pub fn segment_under(problem: &SweepLineProblem, p: &Point, radius: f64) -> Option<usize> {
+    let mut closest = None;
+    let mut closest_distance = radius;
+    for (i, segment) in problem.segments.iter().enumerate() {
+        let d = distance_to_segment(p, segment);
+        if d <= closest_distance {
+            closest = Some(i);
+            closest_distance = d;
+        }
+    }
+    closest
+}
+
+// Segments with both endpoints in the rectangle with corners a and b, in increasing order
+pub fn segments_in_rectangle(problem: &SweepLineProblem, a: &Point, b: &Point) -> Vec<usize> {
+    let (x_min, x_max) = (f64::min(a.x, b.x), f64::max(a.x, b.x));
+    let (y_min, y_max) = (f64::min(a.y, b.y), f64::max(a.y, b.y));
+    let inside = |p: &Point| x_min <= p.x && p.x <= x_max && y_min <= p.y && p.y <= y_max;
+    problem.segments.iter().enumerate()
+        .filter(|(_, segment)| inside(&segment.ini) && inside(&segment.end))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn translated(segment: &Segment, dx: f64, dy: f64) -> Segment {
+    Segment {
+        ini: Point { x: segment.ini.x + dx, y: segment.ini.y + dy },
+        end: Point { x: segment.end.x + dx, y: segment.end.y + dy },
+    }
+}
+
+// Moves the selected segments from where they were to where they are now, as a single edit.
+// `original` holds the selected segments before the move, in the order of `selection`.
+pub fn move_command(problem: &SweepLineProblem, selection: &[usize], original: &[Segment]) -> EditCommand {
+    let mut commands = Vec::new();
+    for (&i, before) in selection.iter().zip(original.iter()) {
+        let after = &problem.segments[i];
+        for (end, from, to) in [(0, &before.ini, &after.ini), (1, &before.end, &after.end)] {
+            if from != to {
+                commands.push(EditCommand::MoveEndpoint { index: i, end, from: from.clone(), to: to.clone() });
+            }
+        }
+    }
+    EditCommand::Bulk(commands)
+}
+
+// Copies of the selected segments displaced by (dx, dy), added after the existing ones
+pub fn duplicate_command(problem: &SweepLineProblem, selection: &[usize], dx: f64, dy: f64) -> EditCommand {
+    let n = problem.segments.len();
+    EditCommand::Bulk(selection.iter().enumerate().map(|(k, &i)| EditCommand::AddSegment {
+        index: n + k,
+        segment: translated(&problem.segments[i], dx, dy),
+        info: problem.segment_info[i].clone(),
+    }).collect())
+}
+
+// Removes the selected segments, from the last one so that the indices stay valid
+pub fn delete_command(problem: &SweepLineProblem, selection: &[usize]) -> EditCommand {
+    let mut selection = selection.to_vec();
+    selection.sort();
+    selection.dedup();
+    EditCommand::Bulk(selection.iter().rev().map(|&i| remove_command(problem, i)).collect())
+}
+
+pub fn test_selection() {
+    let original = create_seeded_example(50, 5);
+    let mut problem = original.clone();
+    let mut history = EditHistory::new();
+
+    let s = Segment { ini: Point { x: 0.0, y: 0.0 }, end: Point { x: 10.0, y: 0.0 } };
+    assert_eq!(distance_to_segment(&Point { x: 5.0, y: 3.0 }, &s), 3.0);
+    assert_eq!(distance_to_segment(&Point { x: -4.0, y: 3.0 }, &s), 5.0);
+    let i = 17;
+    let middle = Point {
+        x: (problem.segments[i].ini.x + problem.segments[i].end.x) / 2.0,
+        y: (problem.segments[i].ini.y + problem.segments[i].end.y) / 2.0,
+    };
+    assert!(distance_to_segment(&middle, &problem.segments[segment_under(&problem, &middle, 5.0).unwrap()]) < 1e-9);
+
+    let everything = segments_in_rectangle(&problem, &Point { x: -1e9, y: 1e9 }, &Point { x: 1e9, y: -1e9 });
+    assert_eq!(everything, (0..50).collect::<Vec<_>>());
+    let selection = segments_in_rectangle(&problem, &Point { x: 0.0, y: 0.0 }, &Point { x: 400.0, y: 300.0 });
+    assert!(!selection.is_empty() && selection.len() < 50);
+
+    // Moving is done live and recorded at the end, like a drag
+    let before: Vec<Segment> = selection.iter().map(|&i| problem.segments[i].clone()).collect();
+    for &i in selection.iter() {
+        problem.segments[i] = translated(&problem.segments[i], 10.0, -20.0);
+    }
+    history.record(move_command(&problem, &selection, &before));
+    history.apply(duplicate_command(&problem, &selection, 10.0, 10.0), &mut problem);
+    assert_eq!(problem.segments.len(), 50 + selection.len());
+    assert!(problem.segments[50] == translated(&before[0], 20.0, -10.0));
+    let all: Vec<usize> = (0..problem.segments.len()).collect();
+    history.apply(delete_command(&problem, &all), &mut problem);
+    assert!(problem.segments.is_empty());
+
+    while history.undo(&mut problem) {}
+    assert!(problem.segments == original.segments && problem.segment_info == original.segment_info);
+}
@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use serde::{Deserialize, Serialize};
+use crate::scene::Theme;
+
+// Read by the viewer at startup, the defaults are used for anything missing
+pub const SETTINGS_PATH: &str = "settings.toml";
+// Limits of the grid size in pixels, for the settings file and the keys that change it
+pub const MIN_GRID_SIZE: f64 = 2.0;
+pub const MAX_GRID_SIZE: f64 = 500.0;
+
+// What a key does in the viewer
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum Action {
+    Save,
+    SaveAs,
+    SaveExpected,
+    Open,
+    NextFile,
+    PreviousFile,
+    Undo,
+    Redo,
+    EditTool,
+    SelectTool,
+    PolylineTool,
+    SelectAll,
+    Duplicate,
+    Delete,
+    Cancel,
+    ToggleColor,
+    RedBlue,
+    Incremental,
+    StepMode,
+    StepForward,
+    StepBack,
+    Play,
+    Faster,
+    Slower,
+    Fit,
+    Compare,
+    NextSolverA,
+    NextSolverB,
+    Hud,
+    Record,
+    RecordFrames,
+    FinerGrid,
+    CoarserGrid,
+    Snap,
+}
+
+// Name of every action in the settings file, with its default keys. Several keys are
+// separated by commas, and Ctrl+ and Shift+ prefixes ask for those modifiers.
+pub const ACTIONS: [(Action, &str, &str); 34] = [
+    (Action::Save, "save", "S"),
+    (Action::SaveAs, "save_as", "Shift+S"),
+    (Action::SaveExpected, "save_expected", "Ctrl+S"),
+    (Action::Open, "open", "O"),
+    (Action::NextFile, "next_file", "PageDown"),
+    (Action::PreviousFile, "previous_file", "PageUp"),
+    (Action::Undo, "undo", "Ctrl+Z"),
+    (Action::Redo, "redo", "Ctrl+Y"),
+    (Action::EditTool, "edit_tool", "E"),
+    (Action::SelectTool, "select_tool", "Q"),
+    (Action::PolylineTool, "polyline_tool", "P"),
+    (Action::SelectAll, "select_all", "Ctrl+A"),
+    (Action::Duplicate, "duplicate", "Ctrl+D"),
+    (Action::Delete, "delete", "Delete, Back"),
+    (Action::Cancel, "cancel", "Escape"),
+    (Action::ToggleColor, "toggle_color", "C"),
+    (Action::RedBlue, "red_blue", "B"),
+    (Action::Incremental, "incremental", "I"),
+    (Action::StepMode, "step_mode", "T"),
+    (Action::StepForward, "step_forward", "Right"),
+    (Action::StepBack, "step_back", "Left"),
+    (Action::Play, "play", "Space"),
+    (Action::Faster, "faster", "Up"),
+    (Action::Slower, "slower", "Down"),
+    (Action::Fit, "fit", "F"),
+    (Action::Compare, "compare", "V"),
+    (Action::NextSolverA, "next_solver_a", "1"),
+    (Action::NextSolverB, "next_solver_b", "2"),
+    (Action::Hud, "hud", "H"),
+    (Action::Record, "record", "R"),
+    (Action::RecordFrames, "record_frames", "Shift+R"),
+    (Action::FinerGrid, "finer_grid", "LBracket"),
+    (Action::CoarserGrid, "coarser_grid", "RBracket"),
+    (Action::Snap, "snap", "G"),
+];
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Opened when no file is given on the command line
+    pub default_problem: String,
+    pub window_width: f32,
+    pub window_height: f32,
+    // Minimum distance in pixels between grid lines, the world spacing follows the zoom
+    pub grid_size: f64,
+    // Whether new and dragged points snap to the grid
+    pub snap_to_grid: bool,
+    // Distance in pixels at which endpoints, segments and intersections are picked
+    pub pick_radius: f64,
+    pub theme: Theme,
+    // Keys by action name, actions left out keep their default keys
+    pub keys: BTreeMap<String, String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_problem: "problems/sweep_line_problem_2.txt".to_string(),
+            window_width: 800.0,
+            window_height: 600.0,
+            grid_size: 10.0,
+            snap_to_grid: true,
+            pick_radius: 5.0,
+            theme: Theme::default(),
+            keys: ACTIONS.iter().map(|(_, name, keys)| (name.to_string(), keys.to_string())).collect(),
+        }
+    }
+}
+
+pub fn parse_settings(text: &str) -> Result<Settings, String> {
+    let settings: Settings = toml::from_str(text).map_err(|error| error.to_string())?;
+    settings.validate()?;
+    settings.key_bindings()?;
+    Ok(settings)
+}
+
+// The settings of the file, or the defaults if there is no file
+pub fn load_settings(path: &str) -> Result<Settings, String> {
+    if !std::path::Path::new(path).exists() {
+        return Ok(Settings::default());
+    }
+    let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    parse_settings(&text).map_err(|error| format!("{}: {}", path, error))
+}
+
+pub fn settings_to_toml(settings: &Settings) -> String {
+    toml::to_string_pretty(settings).unwrap()
+}
+
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+    let letters = [
+        KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+        KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+        KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+        KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    ];
+    let digits = [
+        KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+        KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    ];
+    let function_keys = [
+        KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+        KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    ];
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Some(letters[(c.to_ascii_uppercase() as u8 - b'A') as usize]);
+        }
+        if c.is_ascii_digit() {
+            return Some(digits[(c as u8 - b'0') as usize]);
+        }
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<usize>().ok()) {
+        return function_keys.get(n.wrapping_sub(1)).copied();
+    }
+    Some(match name {
+        "Escape" => KeyCode::Escape,
+        "Space" => KeyCode::Space,
+        "Return" | "Enter" => KeyCode::Return,
+        "Back" | "Backspace" => KeyCode::Back,
+        "Delete" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "Insert" => KeyCode::Insert,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Minus" => KeyCode::Minus,
+        "Equals" => KeyCode::Equals,
+        "Plus" => KeyCode::Plus,
+        "LBracket" => KeyCode::LBracket,
+        "RBracket" => KeyCode::RBracket,
+        "Comma" => KeyCode::Comma,
+        "Period" => KeyCode::Period,
+        "Slash" => KeyCode::Slash,
+        _ => return None,
+    })
+}
+
+// A key with the Ctrl and Shift state it needs
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+pub fn parse_key_binding(text: &str) -> Result<KeyBinding, String> {
+    let mut binding = KeyBinding { key: KeyCode::Escape, ctrl: false, shift: false };
+    let mut parts: Vec<&str> = text.split('+').map(|part| part.trim()).collect();
+    let key = parts.pop().unwrap_or("");
+    for modifier in parts {
+        match modifier {
+            "Ctrl" => binding.ctrl = true,
+            "Shift" => binding.shift = true,
+            _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, text)),
+        }
+    }
+    binding.key = parse_key_code(key).ok_or_else(|| format!("unknown key '{}'", key))?;
+    Ok(binding)
+}
+
+impl Settings {
+    // Sizes the viewer can not work with: a grid size out of its limits, and a window size,
+    // pick radius or theme size that is negative or not a number
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_GRID_SIZE..=MAX_GRID_SIZE).contains(&self.grid_size) {
+            return Err(format!(
+                "grid_size must be between {} and {}, found {}", MIN_GRID_SIZE, MAX_GRID_SIZE, self.grid_size
+            ));
+        }
+        for (name, value) in [
+            ("window_width", self.window_width as f64),
+            ("window_height", self.window_height as f64),
+            ("pick_radius", self.pick_radius),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{} must be positive, found {}", name, value));
+            }
+        }
+        for (name, value) in [
+            ("theme.grid_width", self.theme.grid_width),
+            ("theme.segment_width", self.theme.segment_width),
+            ("theme.endpoint_radius", self.theme.endpoint_radius),
+            ("theme.intersection_width", self.theme.intersection_width),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("{} must not be negative, found {}", name, value));
+            }
+        }
+        Ok(())
+    }
+
+    // Every binding with its action, checking the action names and the keys. A key bound
+    // to two actions is an error, only one of them could ever run.
+    pub fn key_bindings(&self) -> Result<Vec<(KeyBinding, Action)>, String> {
+        for name in self.keys.keys() {
+            if !ACTIONS.iter().any(|(_, action_name, _)| action_name == name) {
+                return Err(format!("unknown action '{}' in keys", name));
+            }
+        }
+        let mut bindings: Vec<(KeyBinding, Action)> = Vec::new();
+        let mut names: Vec<&str> = Vec::new();
+        for (action, name, default_keys) in ACTIONS {
+            let keys = self.keys.get(name).map_or(default_keys, |keys| keys.as_str());
+            for key in keys.split(',').map(|key| key.trim()).filter(|key| !key.is_empty()) {
+                let binding = parse_key_binding(key).map_err(|error| format!("keys.{}: {}", name, error))?;
+                if let Some(k) = bindings.iter().position(|(other, other_action)| *other == binding && *other_action != action) {
+                    return Err(format!("keys.{}: '{}' is already bound to {}", name, key, names[k]));
+                }
+                bindings.push((binding, action));
+                names.push(name);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+// The action of a pressed key, the Ctrl and Shift state has to match the binding
+pub fn find_action(bindings: &[(KeyBinding, Action)], key: KeyCode, mods: KeyMods) -> Option<Action> {
+    bindings.iter()
+        .find(|(binding, _)| {
+            binding.key == key
+                && binding.ctrl == mods.contains(KeyMods::CTRL)
+                && binding.shift == mods.contains(KeyMods::SHIFT)
+        })
+        .map(|(_, action)| *action)
+}
+
+pub fn test_settings() {
+    let defaults = Settings::default();
+    let bindings = defaults.key_bindings().unwrap();
+    assert_eq!(find_action(&bindings, KeyCode::S, KeyMods::empty()), Some(Action::Save));
+    assert_eq!(find_action(&bindings, KeyCode::S, KeyMods::SHIFT), Some(Action::SaveAs));
+    assert_eq!(find_action(&bindings, KeyCode::Z, KeyMods::CTRL), Some(Action::Undo));
+    assert_eq!(find_action(&bindings, KeyCode::Z, KeyMods::empty()), None);
+    assert_eq!(find_action(&bindings, KeyCode::Back, KeyMods::empty()), Some(Action::Delete));
+    assert_eq!(find_action(&bindings, KeyCode::Key2, KeyMods::empty()), Some(Action::NextSolverB));
+
+    // The example file in the repository is the default settings
+    let example = std::fs::read_to_string("settings.example.toml").unwrap();
+    assert_eq!(parse_settings(&example).unwrap(), defaults);
+    assert_eq!(parse_settings(&settings_to_toml(&defaults)).unwrap(), defaults);
+
+    // Anything left out keeps its default
+    let settings = parse_settings(
+        "grid_size = 20.0\nsnap_to_grid = false\n\n[theme]\nbackground = [0.0, 0.0, 0.0, 1.0]\n\n[keys]\nundo = \"Ctrl+U, F2\"\n"
+    ).unwrap();
+    assert_eq!(settings.grid_size, 20.0);
+    assert!(!settings.snap_to_grid);
+    assert_eq!(settings.pick_radius, defaults.pick_radius);
+    assert_eq!(settings.theme.background, [0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(settings.theme.grid, defaults.theme.grid);
+    let bindings = settings.key_bindings().unwrap();
+    assert_eq!(find_action(&bindings, KeyCode::U, KeyMods::CTRL), Some(Action::Undo));
+    assert_eq!(find_action(&bindings, KeyCode::F2, KeyMods::empty()), Some(Action::Undo));
+    assert_eq!(find_action(&bindings, KeyCode::Z, KeyMods::CTRL), None);
+    assert_eq!(find_action(&bindings, KeyCode::Y, KeyMods::CTRL), Some(Action::Redo));
+
+    assert!(parse_settings("[keys]\nfly = \"W\"\n").is_err());
+    assert!(parse_settings("[keys]\nundo = \"Hyper+Z\"\n").is_err());
+    assert!(parse_settings("[keys]\nundo = \"Ctrl+Nothing\"\n").is_err());
+    assert!(parse_settings("grid_size = \"big\"\n").is_err());
+    for text in [
+        "grid_size = -10.0\n", "grid_size = 0.0\n", "grid_size = 1.0\n", "grid_size = 1000.0\n", "grid_size = nan\n",
+        "pick_radius = -5.0\n", "pick_radius = 0.0\n", "window_width = -800.0\n", "window_height = 0.0\n",
+        "window_width = inf\n", "[theme]\nsegment_width = -2.0\n", "[theme]\nintersection_width = nan\n",
+    ] {
+        assert!(parse_settings(text).is_err(), "{}", text);
+    }
+    assert_eq!(parse_settings("grid_size = 2.0\n").unwrap().grid_size, MIN_GRID_SIZE);
+
+    // A key can only do one thing, also when the other action keeps its default keys
+    let Err(error) = parse_settings("[keys]\nundo = \"Ctrl+Z\"\nredo = \"Ctrl+Z\"\n") else { panic!() };
+    assert!(error.contains("Ctrl+Z") && error.contains("undo"), "{}", error);
+    assert!(parse_settings("[keys]\nfit = \"S\"\n").is_err());
+    assert!(parse_settings("[keys]\nfit = \"S\"\nsave = \"F5\"\n").is_ok());
+    assert!(parse_settings("[keys]\nundo = \"Ctrl+Z, Ctrl+Z\"\n").is_ok());
+    assert_eq!(load_settings("no/such/settings.toml").unwrap(), defaults);
+}
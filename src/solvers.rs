@@ -1,16 +1,20 @@
 use std::cmp::Ordering;
 use ggez::input::keyboard::KeyCode::E;
-use std::collections::{HashMap, BinaryHeap};
+use std::collections::{HashMap, HashSet, BinaryHeap};
+use std::time::Instant;
 use crate::domain::{
-    Point, Segment, Direction, SweepLineProblem,
+    Point, Segment, SegmentColor, Direction, SweepLineProblem,
     Treap,
-    segments_intersection
+    segments_intersection, distance
 };
-use crate::example_generator::create_random_example;
+use crate::broad_phase::{grid_intersection_solver, quadtree_intersection_solver};
+use crate::example_generator::{create_random_example, create_seeded_example, create_short_segments_example};
+use crate::trace::TraceRecord;
 
 
 pub fn naive_intersection_solver(sweep_line_problem: &mut SweepLineProblem) {
     sweep_line_problem.result.clear();
+    sweep_line_problem.result_pairs.clear();
     for i in 0..sweep_line_problem.segments.len() {
         for j in i+1..sweep_line_problem.segments.len() {
             let segment_i = &sweep_line_problem.segments[i];
@@ -28,6 +32,7 @@ pub fn naive_intersection_solver(sweep_line_problem: &mut SweepLineProblem) {
                             end: p.end.clone(),
                         });
                     }
+                    sweep_line_problem.result_pairs.push((i, j));
                 }
                 None => (),
             }
@@ -36,9 +41,57 @@ pub fn naive_intersection_solver(sweep_line_problem: &mut SweepLineProblem) {
     }
 }
 
+// Only reports intersections between a red and a blue segment. The sweep keeps one active
+// list per color and a starting segment is only tested against the active segments of the
+// other color, so pairs of the same color are never looked at. It is not output sensitive:
+// every start costs the number of active segments of the other color, so it takes
+// O(n log n + n * k) for at most k segments crossed by the sweep line, whatever the number
+// of intersections.
+pub fn red_blue_intersection_solver(sweep_line_problem: &mut SweepLineProblem) {
+    sweep_line_problem.result.clear();
+    sweep_line_problem.result_pairs.clear();
+    let segments = &sweep_line_problem.segments;
+    let mut events = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let x_min = f64::min(segment.ini.x, segment.end.x);
+        let x_max = f64::max(segment.ini.x, segment.end.x);
+        events.push((x_min, false, i));
+        events.push((x_max, true, i));
+    }
+    // Starts go before ends at the same x so that touching segments are still tested
+    events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then_with(|| a.1.cmp(&b.1)));
+
+    let mut active_red: Vec<usize> = Vec::new();
+    let mut active_blue: Vec<usize> = Vec::new();
+    let mut active_position = vec![0; segments.len()];
+    for (_, is_end, i) in events {
+        let (same, other) = match sweep_line_problem.segment_info[i].color {
+            SegmentColor::Red => (&mut active_red, &active_blue),
+            SegmentColor::Blue => (&mut active_blue, &active_red),
+        };
+        if is_end {
+            let position = active_position[i];
+            same.swap_remove(position);
+            if position < same.len() {
+                active_position[same[position]] = position;
+            }
+        } else {
+            for &j in other.iter() {
+                sweep_line_problem.basic_operations += 1;
+                if let Some(intersection) = segments_intersection(&segments[i], &segments[j]) {
+                    sweep_line_problem.result.push(intersection);
+                    sweep_line_problem.result_pairs.push((usize::min(i, j), usize::max(i, j)));
+                }
+            }
+            active_position[i] = same.len();
+            same.push(i);
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 #[derive(PartialEq, PartialOrd)]
-enum EventType {
+pub enum EventType {
     Start,
     Cross,
     End,
@@ -46,12 +99,12 @@ enum EventType {
 
 #[derive(Clone, Debug)]
 #[derive(PartialEq)]
-struct Event {
-    p1: Point,
-    p2: Point,
-    event_type: EventType,
-    s1_i: usize,
-    s2_i: usize
+pub struct Event {
+    pub p1: Point,
+    pub p2: Point,
+    pub event_type: EventType,
+    pub s1_i: usize,
+    pub s2_i: usize
 }
 
 impl Eq for Event {}
@@ -78,219 +131,413 @@ impl PartialOrd for Event {
     }
 }
 
-fn check_for_intersection(
-    segments: &Vec<Segment>,
-    f_segment_i: usize,
-    s_segment_i: usize,
-    basic_operations: &mut i32
-) -> Option<Event> {
-    *basic_operations += 1;
-    if let Some(intersection) = segments_intersection(
-        &segments[f_segment_i], &segments[s_segment_i]
-    ) {
-        //println!("Adding event at point ({}, {})", p.ini.x, p.ini.y);
-        return Some(Event {
-            p1: intersection.ini,
-            p2: intersection.end,
-            event_type: EventType::Cross,
-            s1_i: f_segment_i,
-            s2_i: s_segment_i
-        });
-    }
-    None
+// Two y coordinates closer than this, relative to their size, are the same point of the
+// sweep line. Intersection points are computed, so they are only this close to the segments.
+const SWEEP_TOLERANCE: f64 = 1e-9;
+
+fn same_height(a: f64, b: f64) -> bool {
+    (a - b).abs() <= SWEEP_TOLERANCE * f64::max(1.0, f64::max(a.abs(), b.abs()))
 }
 
-pub fn sweep_line_solver(sweep_line_problem: &mut SweepLineProblem) {
-    sweep_line_problem.result.clear();
-    let mut events: BinaryHeap<Event> = BinaryHeap::new();
-    let mut segments_list = Vec::new();
-    let mut segments_map = HashMap::new();
-    let mut i: usize = 0;
-    let size = sweep_line_problem.segments.len();
-    for segment in &sweep_line_problem.segments{
-        let mut aux_segment = segment.clone();
-        if aux_segment.ini.x > aux_segment.end.x {
-            aux_segment = Segment {
-                ini: aux_segment.end,
-                end: aux_segment.ini,
-            };
+// State of the sweep between two events, so that it can be run one event at a time
+#[derive(Clone)]
+pub struct SweepLineStepper {
+    events: BinaryHeap<Event>,
+    // Segments from left to right, vertical ones from bottom to top
+    segments_list: Vec<Segment>,
+    // Segments crossed by the sweep line, from bottom to top. The order depends on where
+    // the sweep line is, so the treap is only split and joined, never searched by key.
+    segments_tree: Treap<usize>,
+    // Vertical segments on the sweep line, they are not ordered with the others
+    verticals: Vec<usize>,
+    // Pairs reported so far, two segments can be neighbours again after they meet
+    reported: HashSet<(usize, usize)>,
+    // x the status is ordered at. An intersection found late can be left of it, but the
+    // status never goes back.
+    status_x: f64,
+    iters: usize,
+    size: usize,
+    // x of the last processed event
+    pub sweep_x: Option<f64>,
+    // Index in `result` of the intersection reported by the last step
+    pub last_intersection: Option<usize>,
+    // Neighbour checks of the last step
+    checks: Vec<(usize, usize, Option<Segment>)>,
+    trace: Option<Vec<TraceRecord>>,
+}
+
+impl SweepLineStepper {
+    pub fn new(sweep_line_problem: &mut SweepLineProblem) -> SweepLineStepper {
+        sweep_line_problem.result.clear();
+        sweep_line_problem.result_pairs.clear();
+        let mut events: BinaryHeap<Event> = BinaryHeap::new();
+        let mut segments_list = Vec::new();
+        let size = sweep_line_problem.segments.len();
+        for (i, segment) in sweep_line_problem.segments.iter().enumerate() {
+            let mut aux_segment = segment.clone();
+            if (aux_segment.ini.x, aux_segment.ini.y) > (aux_segment.end.x, aux_segment.end.y) {
+                aux_segment = Segment {
+                    ini: aux_segment.end,
+                    end: aux_segment.ini,
+                };
+            }
+            events.push(Event {
+                p1: aux_segment.ini.clone(),
+                p2: aux_segment.ini.clone(),
+                event_type: EventType::Start,
+                s1_i: i,
+                s2_i: i
+            });
+            events.push(Event {
+                p1: aux_segment.end.clone(),
+                p2: aux_segment.end.clone(),
+                event_type: EventType::End,
+                s1_i: i,
+                s2_i: i
+            });
+            segments_list.push(aux_segment);
+        }
+        SweepLineStepper {
+            events,
+            segments_list,
+            segments_tree: Treap::new(),
+            verticals: Vec::new(),
+            reported: HashSet::new(),
+            status_x: f64::NEG_INFINITY,
+            iters: 0,
+            size,
+            sweep_x: None,
+            last_intersection: None,
+            checks: Vec::new(),
+            trace: None,
         }
-        //if aux_segment.ini.x == aux_segment.end.x { continue; }
-        events.push(Event {
-            p1: aux_segment.ini.clone(),
-            p2: aux_segment.ini.clone(),
-            event_type: EventType::Start,
-            s1_i: i,
-            s2_i: i
-        });
-        events.push(Event {
-            p1: aux_segment.end.clone(),
-            p2: aux_segment.end.clone(),
-            event_type: EventType::End,
-            s1_i: i,
-            s2_i: i
-        });
-        segments_list.push(aux_segment.clone());
-        segments_map.insert(aux_segment, i);
-        i += 1;
     }
-    let mut segments_tree = Treap::new();
-    let mut iters = 0;
-    while !events.is_empty() {
-        iters += 1;
-        if iters > size*size {break}
-        /*println!("--------------------------------");
-        println!("Events");
-        let mut events_clone = events.clone();
-        while let Some(Event {p, event_type, s1_i, s2_i}) = events_clone.pop() {
-            println!("({}, {}) - {:?} - ({}, {})", p.x, p.y, event_type, s1_i, s2_i);
-        }*/
-        let Some(Event {p1, p2, event_type, s1_i, s2_i}) = events.pop()
-            else { panic!("This cant happen!") };
-
-        //segments_tree.print_inorder();
-        match event_type {
-            EventType::Start => {
-                let s1 = &segments_list[s1_i];
-                let successor = segments_tree.successor(
-                    s1, &mut sweep_line_problem.basic_operations);
-                let predecessor = segments_tree.predecessor(
-                    s1, &mut sweep_line_problem.basic_operations);
-                if let Some(successor) = successor {
-                    if let Some(event) = check_for_intersection(
-                        &segments_list, s1_i, segments_map[successor],
-                        &mut sweep_line_problem.basic_operations
-                    ) { events.push(event) }
-                }
-                if let Some(predecessor) = predecessor {
-                    if let Some(event) = check_for_intersection(
-                        &segments_list, segments_map[predecessor], s1_i,
-                        &mut sweep_line_problem.basic_operations
-                    ){ events.push(event) }
-                }
-                segments_tree.insert(s1.clone(), &mut sweep_line_problem.basic_operations);
+
+    // Records every following step, starting with the events queued so far
+    pub fn enable_trace(&mut self) {
+        let mut events = self.events.clone().into_sorted_vec();
+        events.reverse();
+        self.trace = Some(vec![TraceRecord::Init { events }]);
+    }
+
+    pub fn take_trace(&mut self) -> Vec<TraceRecord> {
+        self.trace.take().unwrap_or_default()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.events.is_empty() || self.iters > self.size * self.size
+    }
+
+    // Events still in the queue, in the order they will be processed
+    pub fn pending_events(&self) -> Vec<(Point, EventType)> {
+        let mut events = self.events.clone().into_sorted_vec();
+        events.reverse();
+        events.into_iter().map(|event| (event.p1, event.event_type)).collect()
+    }
+
+    // Indices of the segments in the status, from the first to the last in the treap order,
+    // followed by the vertical segments on the sweep line
+    pub fn status(&self) -> Vec<usize> {
+        let mut status = self.segments_tree.to_vec();
+        status.extend(self.verticals.iter().copied());
+        status
+    }
+
+    // Processes the next event, returns false once there are none left
+    pub fn step(&mut self, sweep_line_problem: &mut SweepLineProblem) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        self.iters += 1;
+        self.last_intersection = None;
+        self.checks.clear();
+        let Some(event) = self.events.pop() else { panic!("This cant happen!") };
+        self.sweep_x = Some(event.p1.x);
+        let status_before = if self.trace.is_some() { self.status() } else { Vec::new() };
+        let found_before = sweep_line_problem.result.len();
+        self.process_event(sweep_line_problem, event.clone());
+
+        if self.trace.is_some() {
+            let step = self.iters - 1;
+            let status_after = self.status();
+            let trace = self.trace.as_mut().unwrap();
+            trace.push(TraceRecord::Event { step, event, status_before, status_after });
+            for k in found_before..sweep_line_problem.result.len() {
+                trace.push(TraceRecord::Intersection {
+                    step,
+                    intersection: sweep_line_problem.result[k].clone(),
+                    segments: sweep_line_problem.result_pairs[k],
+                });
             }
-            EventType::End => {
-                let s1= &segments_list[s1_i];
-                if segments_tree.remove(&s1, &mut sweep_line_problem.basic_operations) {
-                    let predecessor = segments_tree.predecessor(
-                        &s1, &mut sweep_line_problem.basic_operations);
-                    let successor = segments_tree.successor(
-                        &s1, &mut sweep_line_problem.basic_operations);
-                    if let (Some(predecessor), Some(successor)) =
-                        (predecessor, successor) {
-                        if let Some(event) = check_for_intersection(
-                            &segments_list,
-                            segments_map[predecessor],
-                            segments_map[successor],
-                            &mut sweep_line_problem.basic_operations
-                        ) { events.push(event) }
-                    }
-                }
-                else {
-                    /*
-                    println!("ERROR: s1 must be in the tree");
-                    println!("S1: ({}, {}), ({}, {})",
-                             s1.ini.x, s1.ini.y, s1.end.x, s1.end.y);
-                    segments_tree.print_inorder();
-                     */
-                }
+            for (i, j, intersection) in self.checks.iter() {
+                trace.push(TraceRecord::Check { step, segments: (*i, *j), intersection: intersection.clone() });
             }
-            EventType::Cross => {
-                sweep_line_problem.result.push(Segment {
-                    ini: p1.clone(),
-                    end: p2.clone(),
+        }
+        true
+    }
+
+    fn is_vertical(&self, i: usize) -> bool {
+        self.segments_list[i].ini.x == self.segments_list[i].end.x
+    }
+
+    // Lowest and highest point of the segment on the sweep line at x
+    fn span_at(&self, i: usize, x: f64) -> (f64, f64) {
+        let segment = &self.segments_list[i];
+        if self.is_vertical(i) {
+            (segment.ini.y, segment.end.y)
+        } else {
+            let y = segment.interpolate_y(x);
+            (y, y)
+        }
+    }
+
+    fn report(&mut self, sweep_line_problem: &mut SweepLineProblem, a: usize, b: usize, intersection: Segment) {
+        if self.reported.insert((a, b)) {
+            sweep_line_problem.result.push(intersection);
+            sweep_line_problem.result_pairs.push((a, b));
+            self.last_intersection = Some(sweep_line_problem.result.len() - 1);
+        }
+    }
+
+    // Tests the pair on the whole segments. An intersection right of the sweep line becomes
+    // a cross event, one on it or left of it is reported now. Every check is recorded in
+    // `checks` with the intersection it found, for the trace.
+    fn check(&mut self, sweep_line_problem: &mut SweepLineProblem, a: usize, b: usize) {
+        let (a, b) = (usize::min(a, b), usize::max(a, b));
+        if a == b || self.reported.contains(&(a, b)) {
+            return;
+        }
+        sweep_line_problem.basic_operations += 1;
+        let intersection = segments_intersection(&sweep_line_problem.segments[a], &sweep_line_problem.segments[b]);
+        self.checks.push((a, b, intersection.clone()));
+        if let Some(intersection) = intersection {
+            if f64::min(intersection.ini.x, intersection.end.x) > self.status_x {
+                self.events.push(Event {
+                    p1: intersection.ini,
+                    p2: intersection.end,
+                    event_type: EventType::Cross,
+                    s1_i: a,
+                    s2_i: b
                 });
+            } else {
+                self.report(sweep_line_problem, a, b, intersection);
+            }
+        }
+    }
 
-                if segments_list[s1_i].ini == segments_list[s1_i].end ||
-                    segments_list[s2_i].ini == segments_list[s2_i].end ||
-                    p1 == segments_list[s1_i].end || p1 == segments_list[s2_i].end ||
-                    p1 == segments_list[s1_i].ini || p1 == segments_list[s2_i].ini
-                { continue; }
-
-                //println!("Crossing at point ({}, {})", p.x, p.y);
-                if !segments_tree.remove(
-                    &segments_list[s1_i], &mut sweep_line_problem.basic_operations) {
-                    /*println!("S1: ({}, {}), ({}, {})",
-                             segments_list[s1_i].ini.x,
-                             segments_list[s1_i].ini.y,
-                             segments_list[s1_i].end.x,
-                             segments_list[s1_i].end.y);
-                    println!("ERROR: s1 must be in the tree");
-                    segments_tree.print_inorder();*/
-                    continue;
-                }
-                if !segments_tree.remove(
-                    &segments_list[s2_i], &mut sweep_line_problem.basic_operations) {
-                    /*println!("S2: ({}, {}), ({}, {})",
-                             segments_list[s2_i].ini.x,
-                             segments_list[s2_i].ini.y,
-                             segments_list[s2_i].end.x,
-                             segments_list[s2_i].end.y);
-                    println!("ERROR: s2 must be in the tree");
-                    segments_tree.print_inorder();*/
-                    segments_tree.insert(
-                        segments_list[s1_i].clone(), &mut sweep_line_problem.basic_operations
-                    );
-                    continue;
-                }
-                segments_map.remove(&segments_list[s1_i]);
-                segments_map.remove(&segments_list[s2_i]);
+    fn check_pair(&mut self, sweep_line_problem: &mut SweepLineProblem, a: Option<usize>, b: Option<usize>) {
+        if let (Some(a), Some(b)) = (a, b) {
+            self.check(sweep_line_problem, a, b);
+        }
+    }
 
-                segments_list[s1_i] = Segment {
-                    ini: p1.clone(),
-                    end: segments_list[s1_i].end.clone(),
-                };
-                segments_list[s2_i] = Segment {
-                    ini: p1.clone(),
-                    end: segments_list[s2_i].end.clone(),
-                };
+    // Takes the segments between the heights `low` and `high` at x out of the status. The
+    // ones below stay in the returned treap and the ones above in the status.
+    fn split_run(&mut self, sweep_line_problem: &mut SweepLineProblem, x: f64, low: f64, high: f64) -> (Treap<usize>, Vec<usize>) {
+        let segments_list = &self.segments_list;
+        let height = |i: &usize| segments_list[*i].interpolate_y(x);
+        let below = self.segments_tree.split_while(
+            |i| height(i) < low && !same_height(height(i), low), &mut sweep_line_problem.basic_operations);
+        let run = self.segments_tree.split_while(
+            |i| height(i) < high || same_height(height(i), high), &mut sweep_line_problem.basic_operations);
+        (below, run.to_vec())
+    }
 
-                segments_map.insert(segments_list[s1_i].clone(), s1_i);
-                segments_map.insert(segments_list[s2_i].clone(), s2_i);
+    // Puts the run back between the segments below it and the ones above it, and tests the
+    // new neighbours
+    fn join_run(&mut self, sweep_line_problem: &mut SweepLineProblem, below: Treap<usize>, run: Vec<usize>) {
+        let below_last = below.last().copied();
+        let above_first = self.segments_tree.first().copied();
+        self.check_pair(sweep_line_problem, below_last, run.first().copied().or(above_first));
+        self.check_pair(sweep_line_problem, run.last().copied(), above_first);
+        self.rejoin_run(sweep_line_problem, below, run);
+    }
 
-                let successor = segments_tree.successor(
-                    &segments_list[s1_i], &mut sweep_line_problem.basic_operations);
-                let predecessor = segments_tree.predecessor(
-                    &segments_list[s2_i], &mut sweep_line_problem.basic_operations);
+    fn rejoin_run(&mut self, sweep_line_problem: &mut SweepLineProblem, mut below: Treap<usize>, run: Vec<usize>) {
+        for i in run {
+            below.push(i, &mut sweep_line_problem.basic_operations);
+        }
+        let above = std::mem::replace(&mut self.segments_tree, Treap::new());
+        below.append(above, &mut sweep_line_problem.basic_operations);
+        self.segments_tree = below;
+    }
 
-                if let Some(successor) = successor {
-                    let successor_i = segments_map[&successor];
-                    if successor_i == s2_i{
-                        /*println!("S1 successor: ({}, {})", successor.end.x, successor.end.y);
-                        segments_tree.print_inorder();
-                        println!("ERROR: s1 successor and s2 must not be the same");*/
-                    }
-                    else {
-                        if let Some(event) = check_for_intersection(
-                            &segments_list, s1_i, successor_i,
-                            &mut sweep_line_problem.basic_operations
-                        ) { events.push(event) }
-                    }
+    fn process_event(&mut self, sweep_line_problem: &mut SweepLineProblem, event: Event) {
+        let Event {p1, p2, event_type, s1_i, s2_i} = event;
+        // A pair can be queued again while it is waiting, the first of its events swaps it
+        if event_type == EventType::Cross && self.reported.contains(&(s1_i, s2_i)) {
+            return;
+        }
+        let x = f64::max(self.status_x, p1.x);
+        self.status_x = x;
+
+        if event_type == EventType::Cross {
+            self.report(sweep_line_problem, s1_i, s2_i, Segment { ini: p1.clone(), end: p2 });
+        }
+
+        // A vertical segment is all on the sweep line: it meets every segment it spans when
+        // it starts, and starts and ends of other segments at its x come after it starts
+        if event_type != EventType::Cross && self.is_vertical(s1_i) {
+            if event_type == EventType::Start {
+                let (low, high) = self.span_at(s1_i, x);
+                let (below, spanned) = self.split_run(sweep_line_problem, x, low, high);
+                let mut spanned_verticals: Vec<usize> = self.verticals.iter().copied().filter(|&i| {
+                    let (i_low, i_high) = self.span_at(i, x);
+                    (i_low <= high || same_height(i_low, high)) && (low <= i_high || same_height(low, i_high))
+                }).collect();
+                spanned_verticals.extend(spanned.iter().copied());
+                for i in spanned_verticals {
+                    self.check(sweep_line_problem, s1_i, i);
                 }
-                if let Some(predecessor) = predecessor {
-                    let predecessor_i = segments_map[&predecessor];
-                    if predecessor_i == s1_i{
-                        /*println!("S2 predecessor: ({}, {})", predecessor.end.x, predecessor.end.y);
-                        segments_tree.print_inorder();
-                        println!("ERROR: s2 predecessor and s1 must not be the same");*/
-                    }
-                    else {
-                        if let Some(event) = check_for_intersection(
-                            &segments_list, predecessor_i, s2_i,
-                            &mut sweep_line_problem.basic_operations
-                        ) { events.push(event) }
-                    }
+                // The spanned segments keep their order and their neighbours
+                self.rejoin_run(sweep_line_problem, below, spanned);
+                self.verticals.push(s1_i);
+            } else {
+                self.verticals.retain(|&i| i != s1_i);
+            }
+            return;
+        }
+
+        // Every segment through the event point meets every other one there, whether they
+        // are neighbours or not, like three segments crossing at one point or an endpoint
+        // on the inside of another segment
+        let (below, mut run) = self.split_run(sweep_line_problem, x, p1.y, p1.y);
+        if event_type == EventType::End && !run.contains(&s1_i) {
+            // The segment ends on the sweep line, so it is only out of the run at its end
+            // point if rounding put it somewhere else, then it is looked for everywhere
+            self.rejoin_run(sweep_line_problem, below, run);
+            let status = self.segments_tree.to_vec();
+            if let Some(position) = status.iter().position(|&i| i == s1_i) {
+                let mut segments_tree = Treap::new();
+                for &i in status.iter().filter(|&&i| i != s1_i) {
+                    segments_tree.push(i, &mut sweep_line_problem.basic_operations);
                 }
-                segments_tree.insert(
-                    segments_list[s1_i].clone(), &mut sweep_line_problem.basic_operations);
-                segments_tree.insert(
-                    segments_list[s2_i].clone(), &mut sweep_line_problem.basic_operations);
+                self.segments_tree = segments_tree;
+                let neighbour = |k: usize| status.get(k).copied();
+                self.check_pair(sweep_line_problem, position.checked_sub(1).and_then(neighbour), neighbour(position + 1));
             }
+            return;
         }
+        if event_type == EventType::Start {
+            run.push(s1_i);
+        }
+        let verticals: Vec<usize> = self.verticals.iter().copied().filter(|&i| {
+            let (low, high) = self.span_at(i, x);
+            (low <= p1.y || same_height(low, p1.y)) && (p1.y <= high || same_height(high, p1.y))
+        }).collect();
+        sweep_line_problem.basic_operations += run.len() as i32;
+        for (k, &a) in run.iter().enumerate() {
+            for &b in run[k + 1..].iter().chain(verticals.iter()) {
+                self.check(sweep_line_problem, a, b);
+            }
+        }
+        if event_type == EventType::End {
+            run.retain(|&i| i != s1_i);
+        }
+
+        // The run goes back in the order it has right after x, which swaps the segments
+        // crossing here, and the new neighbours are tested, so two segments are tested
+        // before they cross
+        let segments_list = &self.segments_list;
+        run.sort_by(|a, b| segments_list[*a].slope().total_cmp(&segments_list[*b].slope()).then(a.cmp(b)));
+        self.join_run(sweep_line_problem, below, run);
     }
 }
 
+pub fn sweep_line_solver(sweep_line_problem: &mut SweepLineProblem) {
+    let mut stepper = SweepLineStepper::new(sweep_line_problem);
+    while stepper.step(sweep_line_problem) {}
+}
+
+// Same as `sweep_line_solver`, also returning the trace of every step
+pub fn traced_sweep_line_solver(sweep_line_problem: &mut SweepLineProblem) -> Vec<TraceRecord> {
+    let mut stepper = SweepLineStepper::new(sweep_line_problem);
+    stepper.enable_trace();
+    while stepper.step(sweep_line_problem) {}
+    stepper.take_trace()
+}
+
+pub type Solver = fn(&mut SweepLineProblem);
+
+// Solvers that can be chosen by name, in the viewer and the benchmarks
+pub const SOLVERS: [(&str, Solver); 5] = [
+    ("naive", naive_intersection_solver),
+    ("sweep line", sweep_line_solver),
+    ("red-blue", red_blue_intersection_solver),
+    ("grid", grid_intersection_solver),
+    ("quadtree", quadtree_intersection_solver),
+];
+
+pub fn find_solver(name: &str) -> Option<Solver> {
+    SOLVERS.iter().find(|(solver_name, _)| *solver_name == name).map(|(_, solver)| *solver)
+}
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct SolverRun {
+    pub name: &'static str,
+    pub basic_operations: i32,
+    // Seconds
+    pub time: f64,
+    pub found: usize,
+}
+
+// Intersections of two solvers on the same segments, matched by segment pair and position
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct SolverComparison {
+    pub a: SolverRun,
+    pub b: SolverRun,
+    pub both: Vec<Segment>,
+    pub only_a: Vec<Segment>,
+    pub only_b: Vec<Segment>,
+}
+
+fn run_solver(problem: &SweepLineProblem, solver: usize) -> (SolverRun, SweepLineProblem) {
+    let (name, solve) = SOLVERS[solver];
+    let mut problem = problem.clone();
+    problem.basic_operations = 0;
+    let start = Instant::now();
+    solve(&mut problem);
+    problem.time = start.elapsed().as_secs_f64();
+    let run = SolverRun {
+        name,
+        basic_operations: problem.basic_operations,
+        time: problem.time,
+        found: problem.result.len(),
+    };
+    (run, problem)
+}
+
+// Runs the solvers at the given indices of SOLVERS
+pub fn compare_solvers(problem: &SweepLineProblem, a: usize, b: usize) -> SolverComparison {
+    let (a_run, a_problem) = run_solver(problem, a);
+    let (b_run, b_problem) = run_solver(problem, b);
+    let mut b_found: HashMap<(usize, usize), Segment> = normalized_intersections(&b_problem)
+        .into_iter()
+        .map(|(i, j, intersection)| ((i, j), intersection))
+        .collect();
+    let mut comparison = SolverComparison {
+        a: a_run, b: b_run, both: Vec::new(), only_a: Vec::new(), only_b: Vec::new()
+    };
+    for (i, j, intersection) in normalized_intersections(&a_problem) {
+        match b_found.remove(&(i, j)) {
+            Some(other) if same_position(&intersection, &other) => comparison.both.push(intersection),
+            Some(other) => {
+                comparison.only_a.push(intersection);
+                comparison.only_b.push(other);
+            }
+            None => comparison.only_a.push(intersection),
+        }
+    }
+    let mut only_b: Vec<((usize, usize), Segment)> = b_found.into_iter().collect();
+    only_b.sort_by(|x, y| x.0.cmp(&y.0));
+    comparison.only_b.extend(only_b.into_iter().map(|(_, intersection)| intersection));
+    comparison
+}
+
 
 
 pub fn test_sweep_line_solver() {
@@ -337,4 +584,162 @@ pub fn test_sweep_line_solver() {
     for (naive, sweep_line) in naive_basic_operations.iter().zip(sweep_line_basic_operations.iter()) {
         println!("{}, {}", naive, sweep_line);
     }
-}
\ No newline at end of file
+}
+pub fn test_red_blue_solver() {
+    let pairs = |problem: &SweepLineProblem| -> Vec<(Segment, (usize, usize))> {
+        problem.result.iter().cloned().zip(problem.result_pairs.iter().cloned()).collect()
+    };
+    for _ in 0..10 {
+        let mut red_blue_problem = create_random_example(1000);
+        let mut naive_problem = red_blue_problem.clone();
+        red_blue_intersection_solver(&mut red_blue_problem);
+        naive_intersection_solver(&mut naive_problem);
+        // Only the pairs of segments of different colors
+        let expected: Vec<(Segment, (usize, usize))> = pairs(&naive_problem).into_iter()
+            .filter(|(_, (i, j))| naive_problem.segment_info[*i].color != naive_problem.segment_info[*j].color)
+            .collect();
+        assert!(same_intersections(&pairs(&red_blue_problem), &expected));
+        assert!(red_blue_problem.result_pairs.iter().all(|(i, j)| i < j));
+    }
+}
+
+pub fn test_sweep_line_stepper() {
+    for i in 0..10 {
+        let mut stepped_problem = create_random_example(200);
+        let mut solved_problem = stepped_problem.clone();
+        sweep_line_solver(&mut solved_problem);
+
+        let mut stepper = SweepLineStepper::new(&mut stepped_problem);
+        let mut steps = 0;
+        while !stepper.is_done() {
+            let pending = stepper.pending_events();
+            assert!(stepper.step(&mut stepped_problem));
+            steps += 1;
+            // The sweep moves to the first of the pending events
+            assert_eq!(stepper.sweep_x, Some(pending[0].0.x));
+            if let Some(k) = stepper.last_intersection {
+                assert_eq!(k, stepped_problem.result.len() - 1);
+            }
+            assert!(stepper.status().len() <= stepped_problem.segments.len());
+        }
+        assert!(!stepper.step(&mut stepped_problem));
+        assert!(stepped_problem.result == solved_problem.result);
+        println!("{}: {} steps, {} intersections", i, steps, stepped_problem.result.len());
+    }
+}
+
+// Sorted by segment pair, without duplicates and with overlaps pointing the same way
+fn normalize_intersections(intersections: &[(Segment, (usize, usize))]) -> Vec<(usize, usize, Segment)> {
+    let mut normalized: Vec<(usize, usize, Segment)> = intersections.iter()
+        .map(|(segment, (i, j))| {
+            let segment = if (segment.end.x, segment.end.y) < (segment.ini.x, segment.ini.y) {
+                Segment { ini: segment.end.clone(), end: segment.ini.clone() }
+            } else {
+                segment.clone()
+            };
+            (usize::min(*i, *j), usize::max(*i, *j), segment)
+        })
+        .collect();
+    normalized.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    normalized.dedup_by(|a, b| (a.0, a.1) == (b.0, b.1));
+    normalized
+}
+
+fn normalized_intersections(problem: &SweepLineProblem) -> Vec<(usize, usize, Segment)> {
+    let found: Vec<(Segment, (usize, usize))> = problem.result.iter().cloned()
+        .zip(problem.result_pairs.iter().cloned())
+        .collect();
+    normalize_intersections(&found)
+}
+
+fn same_position(a: &Segment, b: &Segment) -> bool {
+    distance(&a.ini, &b.ini) < 1e-6 && distance(&a.end, &b.end) < 1e-6
+}
+
+// Same intersections regardless of order, duplicates and the direction of overlaps
+pub fn same_intersections(a: &[(Segment, (usize, usize))], b: &[(Segment, (usize, usize))]) -> bool {
+    let a = normalize_intersections(a);
+    let b = normalize_intersections(b);
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| {
+        (x.0, x.1) == (y.0, y.1) && same_position(&x.2, &y.2)
+    })
+}
+
+// Every problem file with an expected section is a regression test for the sweep
+pub fn test_problem_files() {
+    let mut paths: Vec<_> = std::fs::read_dir("problems").unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "txt"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let mut problem = SweepLineProblem::load(path.to_str().unwrap()).unwrap();
+        let Some(expected) = problem.expected.clone() else { continue };
+        // The file is checked with the solver that found its expected intersections
+        let Some(solver) = find_solver(&problem.expected_solver) else {
+            panic!("{}: unknown solver '{}'", path.display(), problem.expected_solver)
+        };
+        solver(&mut problem);
+        let found: Vec<(Segment, (usize, usize))> = problem.result.iter().cloned()
+            .zip(problem.result_pairs.iter().cloned())
+            .collect();
+        println!("{}: {} expected, {} found by {}", path.display(), expected.len(), found.len(), problem.expected_solver);
+        assert!(same_intersections(&expected, &found), "{} differs", path.display());
+    }
+}
+
+pub fn test_compare_solvers() {
+    let problem = create_random_example(300);
+    let naive = SOLVERS.iter().position(|(name, _)| *name == "naive").unwrap();
+    let red_blue = SOLVERS.iter().position(|(name, _)| *name == "red-blue").unwrap();
+
+    let comparison = compare_solvers(&problem, naive, naive);
+    assert!(comparison.only_a.is_empty() && comparison.only_b.is_empty());
+    assert_eq!(comparison.both.len(), comparison.a.found);
+    assert_eq!(comparison.a.basic_operations, comparison.b.basic_operations);
+
+    // The red-blue solver skips the pairs of the same color and finds nothing else
+    let comparison = compare_solvers(&problem, naive, red_blue);
+    assert!(comparison.only_b.is_empty());
+    assert_eq!(comparison.both.len(), comparison.b.found);
+    assert_eq!(comparison.both.len() + comparison.only_a.len(), comparison.a.found);
+    for (name, solver) in SOLVERS.iter().enumerate().map(|(i, (name, _))| (name, i)) {
+        let comparison = compare_solvers(&problem, naive, solver);
+        println!(
+            "{}: {} found, {} basic operations, {:.3} ms, {} only found by naive, {} only by {}",
+            name, comparison.b.found, comparison.b.basic_operations, comparison.b.time * 1000.0,
+            comparison.only_a.len(), comparison.only_b.len(), name
+        );
+    }
+}
+
+// Every solver on a few kinds of input, to choose one per dataset. The naive solver is left
+// out above NAIVE_BENCHMARK_LIMIT segments and the red-blue one only finds half the pairs.
+const NAIVE_BENCHMARK_LIMIT: usize = 5000;
+
+pub fn benchmark_solvers() {
+    let mut segments = create_short_segments_example(20000, 2000.0, 30.0, 3).segments;
+    segments.extend(create_seeded_example(100, 3).segments.iter().map(|s| Segment {
+        ini: Point { x: s.ini.x * 2.0, y: s.ini.y * 2.0 },
+        end: Point { x: s.end.x * 2.0, y: s.end.y * 2.0 },
+    }));
+    let mixed = SweepLineProblem::new(segments);
+    let datasets = [
+        ("1000 long segments", create_seeded_example(1000, 3)),
+        ("20000 short segments", create_short_segments_example(20000, 2000.0, 30.0, 3)),
+        ("100000 sparse short segments", create_short_segments_example(100000, 20000.0, 60.0, 3)),
+        ("20000 short and 100 long segments", mixed),
+    ];
+    for (name, problem) in datasets {
+        println!("{}", name);
+        println!("solver, ms, basic operations, intersections");
+        for (solver, (solver_name, _)) in SOLVERS.iter().enumerate() {
+            if *solver_name == "naive" && problem.segments.len() > NAIVE_BENCHMARK_LIMIT {
+                println!("{}, skipped", solver_name);
+                continue;
+            }
+            let (run, _) = run_solver(&problem, solver);
+            println!("{}, {:.3}, {}, {}", run.name, run.time * 1000.0, run.basic_operations, run.found);
+        }
+    }
+}
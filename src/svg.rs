@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use crate::domain::{Point, SegmentColor, SweepLineProblem};
+use crate::solvers::sweep_line_solver;
+
+pub struct SvgOptions {
+    // Visible region as (min x, min y, width, height), the bounding box of the
+    // segments with a margin if None
+    pub view_box: Option<(f64, f64, f64, f64)>,
+    // Pixels per problem unit of the output image
+    pub scale: f64,
+    // Spacing of the background grid, no grid if None
+    pub grid_size: Option<f64>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions { view_box: None, scale: 1.0, grid_size: Some(10.0) }
+    }
+}
+
+// Same colors as the viewer
+const BACKGROUND: &str = "rgb(26,51,77)";
+const GRID: &str = "rgb(128,128,128)";
+const GRID_OPACITY: f64 = 0.2;
+const RED_SEGMENT: &str = "rgb(255,255,255)";
+const BLUE_SEGMENT: &str = "rgb(102,179,255)";
+const INTERSECTION: &str = "rgb(255,0,0)";
+const MARGIN: f64 = 10.0;
+
+fn bounding_box(problem: &SweepLineProblem) -> (f64, f64, f64, f64) {
+    let points: Vec<&Point> = problem.segments.iter()
+        .flat_map(|segment| [&segment.ini, &segment.end])
+        .collect();
+    if points.is_empty() {
+        return (0.0, 0.0, 800.0, 600.0);
+    }
+    let x_min = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) - MARGIN;
+    let y_min = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) - MARGIN;
+    let x_max = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) + MARGIN;
+    let y_max = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) + MARGIN;
+    (x_min, y_min, x_max - x_min, y_max - y_min)
+}
+
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+// A grid size or a scale that is not positive and finite would never finish the grid or
+// give sizes that can not be drawn
+fn check_options(options: &SvgOptions) -> Result<(), String> {
+    if !is_positive(options.scale) {
+        return Err(format!("scale must be a positive number, found {}", options.scale));
+    }
+    if let Some(grid_size) = options.grid_size.filter(|&grid_size| !is_positive(grid_size)) {
+        return Err(format!("grid size must be a positive number, found {}", grid_size));
+    }
+    if let Some((x, y, width, height)) = options.view_box {
+        if !(x.is_finite() && y.is_finite() && is_positive(width) && is_positive(height)) {
+            return Err(format!("invalid view box {} {} {} {}", x, y, width, height));
+        }
+    }
+    Ok(())
+}
+
+// Draws the segments with their endpoints and the current `result` on top. Line widths
+// and radii are given in pixels like in the viewer, so they do not change with the scale.
+pub fn to_svg(problem: &SweepLineProblem, options: &SvgOptions) -> Result<String, String> {
+    check_options(options)?;
+    let (x, y, width, height) = options.view_box.unwrap_or_else(|| bounding_box(problem));
+    let pixel = 1.0 / options.scale;
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">",
+        width * options.scale, height * options.scale, x, y, width, height
+    ).unwrap();
+    writeln!(svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+             x, y, width, height, BACKGROUND).unwrap();
+
+    if let Some(grid_size) = options.grid_size {
+        writeln!(svg, "<g stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\">",
+                 GRID, GRID_OPACITY, pixel).unwrap();
+        let mut grid_x = (x / grid_size).ceil() * grid_size;
+        while grid_x <= x + width {
+            writeln!(svg, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+                     grid_x, y, grid_x, y + height).unwrap();
+            grid_x += grid_size;
+        }
+        let mut grid_y = (y / grid_size).ceil() * grid_size;
+        while grid_y <= y + height {
+            writeln!(svg, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+                     x, grid_y, x + width, grid_y).unwrap();
+            grid_y += grid_size;
+        }
+        writeln!(svg, "</g>").unwrap();
+    }
+
+    writeln!(svg, "<g stroke-width=\"{}\">", 2.0 * pixel).unwrap();
+    for (segment, info) in problem.segments.iter().zip(problem.segment_info.iter()) {
+        let color = match info.color {
+            SegmentColor::Red => RED_SEGMENT,
+            SegmentColor::Blue => BLUE_SEGMENT,
+        };
+        writeln!(svg, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\"/>",
+                 segment.ini.x, segment.ini.y, segment.end.x, segment.end.y, color).unwrap();
+        for p in [&segment.ini, &segment.end] {
+            writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>",
+                     p.x, p.y, 2.0 * pixel, color).unwrap();
+        }
+    }
+    writeln!(svg, "</g>").unwrap();
+
+    writeln!(svg, "<g stroke=\"{}\" fill=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\">",
+             INTERSECTION, INTERSECTION, 5.0 * pixel).unwrap();
+    for intersection in problem.result.iter() {
+        if intersection.ini != intersection.end {
+            writeln!(svg, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+                     intersection.ini.x, intersection.ini.y,
+                     intersection.end.x, intersection.end.y).unwrap();
+        }
+        for p in [&intersection.ini, &intersection.end] {
+            writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" stroke=\"none\"/>",
+                     p.x, p.y, 5.0 * pixel).unwrap();
+        }
+    }
+    writeln!(svg, "</g>").unwrap();
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+pub fn save_svg(problem: &SweepLineProblem, path: &str, options: &SvgOptions) -> Result<(), String> {
+    let svg = to_svg(problem, options).map_err(|error| format!("{}: {}", path, error))?;
+    std::fs::write(path, svg).map_err(|error| format!("{}: {}", path, error))
+}
+
+pub fn test_svg() {
+    let mut problem = SweepLineProblem::load("problems/sweep_line_problem_0.txt").unwrap();
+    sweep_line_solver(&mut problem);
+    let svg = to_svg(&problem, &SvgOptions { grid_size: None, ..Default::default() }).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<line").count(), problem.segments.len());
+    assert_eq!(svg.matches("<circle").count(), 2 * problem.segments.len() + 2 * problem.result.len());
+    // Bounding box of the segments plus the margin
+    assert!(svg.contains("viewBox=\"240 190 370 220\""));
+
+    let svg = to_svg(&problem, &SvgOptions {
+        view_box: Some((0.0, 0.0, 800.0, 600.0)),
+        scale: 0.5,
+        grid_size: Some(100.0),
+    }).unwrap();
+    assert!(svg.contains("width=\"400\" height=\"300\""));
+    assert_eq!(svg.matches("<line").count(), problem.segments.len() + 9 + 7);
+
+    for options in [
+        SvgOptions { grid_size: Some(-10.0), ..Default::default() },
+        SvgOptions { grid_size: Some(0.0), ..Default::default() },
+        SvgOptions { grid_size: Some(f64::NAN), ..Default::default() },
+        SvgOptions { scale: 0.0, ..Default::default() },
+        SvgOptions { scale: -1.0, ..Default::default() },
+        SvgOptions { scale: f64::INFINITY, ..Default::default() },
+        SvgOptions { view_box: Some((f64::NEG_INFINITY, 0.0, 800.0, 600.0)), ..Default::default() },
+        SvgOptions { view_box: Some((0.0, 0.0, 0.0, 600.0)), ..Default::default() },
+    ] {
+        assert!(to_svg(&problem, &options).is_err());
+    }
+    let path = std::env::temp_dir().join("sweep_line_invalid.svg");
+    let path = path.to_str().unwrap();
+    assert!(save_svg(&problem, path, &SvgOptions { scale: 0.0, ..Default::default() })
+        .is_err_and(|error| error.contains("scale")));
+}
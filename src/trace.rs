@@ -0,0 +1,303 @@
+use serde_json::{json, Value};
+use crate::domain::{Point, Segment, SweepLineProblem};
+use crate::example_generator::create_seeded_example;
+use crate::solvers::{sweep_line_solver, traced_sweep_line_solver, Event, EventType, SweepLineStepper};
+
+// One entry of the trace of the sweep. Steps count the popped events from 0.
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum TraceRecord {
+    // Events queued before the first step, in the order they will be popped
+    Init { events: Vec<Event> },
+    // Event popped by the step, with the status (segment indices in order) around it
+    Event { step: usize, event: Event, status_before: Vec<usize>, status_after: Vec<usize> },
+    // Intersection reported by the step
+    Intersection { step: usize, intersection: Segment, segments: (usize, usize) },
+    // Neighbours tested by the step, a found intersection is queued as a cross event
+    Check { step: usize, segments: (usize, usize), intersection: Option<Segment> },
+}
+
+fn point_to_json(p: &Point) -> Value {
+    json!([p.x, p.y])
+}
+
+fn segment_to_json(s: &Segment) -> Value {
+    json!([point_to_json(&s.ini), point_to_json(&s.end)])
+}
+
+fn event_type_name(event_type: &EventType) -> &'static str {
+    match event_type {
+        EventType::Start => "start",
+        EventType::Cross => "cross",
+        EventType::End => "end",
+    }
+}
+
+fn event_to_json(event: &Event) -> Value {
+    json!({
+        "type": event_type_name(&event.event_type),
+        "point": point_to_json(&event.p1),
+        "point2": point_to_json(&event.p2),
+        "segments": [event.s1_i, event.s2_i],
+    })
+}
+
+pub fn record_to_json(record: &TraceRecord) -> Value {
+    match record {
+        TraceRecord::Init { events } => json!({
+            "kind": "init",
+            "events": events.iter().map(event_to_json).collect::<Vec<_>>(),
+        }),
+        TraceRecord::Event { step, event, status_before, status_after } => json!({
+            "kind": "event",
+            "step": step,
+            "event": event_to_json(event),
+            "status_before": status_before,
+            "status_after": status_after,
+        }),
+        TraceRecord::Intersection { step, intersection, segments } => json!({
+            "kind": "intersection",
+            "step": step,
+            "intersection": segment_to_json(intersection),
+            "segments": [segments.0, segments.1],
+        }),
+        TraceRecord::Check { step, segments, intersection } => json!({
+            "kind": "check",
+            "step": step,
+            "segments": [segments.0, segments.1],
+            "intersection": intersection.as_ref().map(segment_to_json),
+        }),
+    }
+}
+
+fn point_from_json(value: &Value) -> Result<Point, String> {
+    match (value[0].as_f64(), value[1].as_f64()) {
+        (Some(x), Some(y)) => Ok(Point { x, y }),
+        _ => Err(format!("expected a point, found {}", value)),
+    }
+}
+
+fn segment_from_json(value: &Value) -> Result<Segment, String> {
+    Ok(Segment { ini: point_from_json(&value[0])?, end: point_from_json(&value[1])? })
+}
+
+fn index_from_json(value: &Value) -> Result<usize, String> {
+    value.as_u64().map(|i| i as usize).ok_or_else(|| format!("expected an index, found {}", value))
+}
+
+fn pair_from_json(value: &Value) -> Result<(usize, usize), String> {
+    Ok((index_from_json(&value[0])?, index_from_json(&value[1])?))
+}
+
+fn indices_from_json(value: &Value) -> Result<Vec<usize>, String> {
+    value.as_array()
+        .ok_or_else(|| format!("expected a list of indices, found {}", value))?
+        .iter()
+        .map(index_from_json)
+        .collect()
+}
+
+fn event_from_json(value: &Value) -> Result<Event, String> {
+    let event_type = match value["type"].as_str() {
+        Some("start") => EventType::Start,
+        Some("cross") => EventType::Cross,
+        Some("end") => EventType::End,
+        _ => return Err(format!("unknown event type {}", value["type"])),
+    };
+    let (s1_i, s2_i) = pair_from_json(&value["segments"])?;
+    Ok(Event {
+        p1: point_from_json(&value["point"])?,
+        p2: point_from_json(&value["point2"])?,
+        event_type,
+        s1_i,
+        s2_i,
+    })
+}
+
+pub fn record_from_json(value: &Value) -> Result<TraceRecord, String> {
+    let step = || index_from_json(&value["step"]);
+    match value["kind"].as_str() {
+        Some("init") => Ok(TraceRecord::Init {
+            events: value["events"].as_array()
+                .ok_or_else(|| "init without events".to_string())?
+                .iter()
+                .map(event_from_json)
+                .collect::<Result<_, _>>()?,
+        }),
+        Some("event") => Ok(TraceRecord::Event {
+            step: step()?,
+            event: event_from_json(&value["event"])?,
+            status_before: indices_from_json(&value["status_before"])?,
+            status_after: indices_from_json(&value["status_after"])?,
+        }),
+        Some("intersection") => Ok(TraceRecord::Intersection {
+            step: step()?,
+            intersection: segment_from_json(&value["intersection"])?,
+            segments: pair_from_json(&value["segments"])?,
+        }),
+        Some("check") => Ok(TraceRecord::Check {
+            step: step()?,
+            segments: pair_from_json(&value["segments"])?,
+            intersection: match &value["intersection"] {
+                Value::Null => None,
+                intersection => Some(segment_from_json(intersection)?),
+            },
+        }),
+        _ => Err(format!("unknown record kind {}", value["kind"])),
+    }
+}
+
+// JSON Lines, one record per line
+pub fn trace_to_json_lines(trace: &[TraceRecord]) -> String {
+    let mut text = String::new();
+    for record in trace {
+        text.push_str(&record_to_json(record).to_string());
+        text.push('\n');
+    }
+    text
+}
+
+pub fn parse_trace(text: &str) -> Result<Vec<TraceRecord>, String> {
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let value: Value = serde_json::from_str(line)
+                .map_err(|error| format!("line {}: {}", i + 1, error))?;
+            record_from_json(&value).map_err(|error| format!("line {}: {}", i + 1, error))
+        })
+        .collect()
+}
+
+pub fn save_trace(trace: &[TraceRecord], path: &str) -> Result<(), String> {
+    std::fs::write(path, trace_to_json_lines(trace)).map_err(|error| format!("{}: {}", path, error))
+}
+
+pub fn load_trace(path: &str) -> Result<Vec<TraceRecord>, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse_trace(&text).map_err(|error| format!("{}: {}", path, error))
+}
+
+// State of the sweep after some number of steps, as read from a trace
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct ReplayState {
+    pub steps: usize,
+    pub sweep_x: Option<f64>,
+    pub status: Vec<usize>,
+    // Queued events, in the order they will be popped
+    pub pending: Vec<Event>,
+    pub result: Vec<Segment>,
+    pub result_pairs: Vec<(usize, usize)>,
+    pub last_event: Option<Event>,
+}
+
+pub struct TraceReplayer {
+    trace: Vec<TraceRecord>,
+    pub n_steps: usize,
+}
+
+impl TraceReplayer {
+    pub fn new(trace: Vec<TraceRecord>) -> TraceReplayer {
+        let n_steps = trace.iter().filter(|record| matches!(record, TraceRecord::Event { .. })).count();
+        TraceReplayer { trace, n_steps }
+    }
+
+    // Rebuilds the state from the records of the first `steps` steps, the solver is not run
+    pub fn state_at(&self, steps: usize) -> ReplayState {
+        let mut state = ReplayState {
+            steps: 0,
+            sweep_x: None,
+            status: Vec::new(),
+            pending: Vec::new(),
+            result: Vec::new(),
+            result_pairs: Vec::new(),
+            last_event: None,
+        };
+        // Pairs reported by the current step. A check that reports its pair right away, when
+        // they meet on the sweep line, does not queue an event.
+        let mut reported = Vec::new();
+        for record in self.trace.iter() {
+            match record {
+                TraceRecord::Init { events } => state.pending = events.clone(),
+                TraceRecord::Event { step, event, status_after, .. } if *step < steps => {
+                    reported.clear();
+                    if let Some(position) = state.pending.iter().position(|pending| pending == event) {
+                        state.pending.remove(position);
+                    }
+                    state.steps = step + 1;
+                    state.sweep_x = Some(event.p1.x);
+                    state.status = status_after.clone();
+                    state.last_event = Some(event.clone());
+                }
+                TraceRecord::Intersection { step, intersection, segments } if *step < steps => {
+                    state.result.push(intersection.clone());
+                    state.result_pairs.push(*segments);
+                    reported.push(*segments);
+                }
+                TraceRecord::Check { step, segments, intersection: Some(intersection) }
+                    if *step < steps && !reported.contains(segments) => {
+                    state.pending.push(Event {
+                        p1: intersection.ini.clone(),
+                        p2: intersection.end.clone(),
+                        event_type: EventType::Cross,
+                        s1_i: segments.0,
+                        s2_i: segments.1,
+                    });
+                }
+                _ => (),
+            }
+        }
+        // Events are ordered for a max heap, the greatest one is popped first
+        state.pending.sort_by(|a, b| b.cmp(a));
+        state
+    }
+}
+
+pub fn test_trace() {
+    // Random segments, and a vertical segment and an endpoint on another segment, whose
+    // intersections are reported as soon as they are found on the sweep line
+    let on_sweep_line = SweepLineProblem::new(vec![
+        Segment { ini: Point { x: 0.0, y: 0.0 }, end: Point { x: 10.0, y: 10.0 } },
+        Segment { ini: Point { x: 0.0, y: 10.0 }, end: Point { x: 10.0, y: 0.0 } },
+        Segment { ini: Point { x: 3.0, y: -1.0 }, end: Point { x: 3.0, y: 11.0 } },
+        Segment { ini: Point { x: 6.0, y: 4.0 }, end: Point { x: 9.0, y: 20.0 } },
+        Segment { ini: Point { x: 1.0, y: 5.0 }, end: Point { x: 9.0, y: 5.0 } },
+    ]);
+    let mut trace = Vec::new();
+    for mut problem in [create_seeded_example(150, 3), on_sweep_line] {
+        let mut solved_problem = problem.clone();
+        sweep_line_solver(&mut solved_problem);
+        trace = traced_sweep_line_solver(&mut problem);
+        assert!(problem.result == solved_problem.result);
+
+        let parsed = parse_trace(&trace_to_json_lines(&trace)).unwrap();
+        assert!(parsed == trace);
+
+        // Every replayed step matches the stepped solver
+        let replayer = TraceReplayer::new(parsed);
+        let mut stepped_problem = problem.clone();
+        let mut stepper = SweepLineStepper::new(&mut stepped_problem);
+        for steps in 0..=replayer.n_steps {
+            let state = replayer.state_at(steps);
+            assert_eq!(state.steps, steps);
+            assert_eq!(state.sweep_x, stepper.sweep_x);
+            assert_eq!(state.status, stepper.status());
+            assert_eq!(state.pending.len(), stepper.pending_events().len());
+            assert!(state.result == stepped_problem.result);
+            stepper.step(&mut stepped_problem);
+        }
+        assert!(stepper.is_done());
+        println!("{} steps, {} records", replayer.n_steps, trace.len());
+    }
+
+    assert!(parse_trace("{\"kind\": \"event\", \"step\": 0}").is_err());
+    assert!(parse_trace("not json").is_err());
+
+    let path = std::env::temp_dir().join("sweep_line_trace.jsonl");
+    save_trace(&trace, path.to_str().unwrap()).unwrap();
+    assert!(load_trace(path.to_str().unwrap()).unwrap() == trace);
+    let unwritable = std::env::temp_dir().join("sweep_line_missing_directory").join("trace.jsonl");
+    assert!(save_trace(&trace, unwritable.to_str().unwrap()).is_err());
+}
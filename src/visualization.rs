@@ -1,3 +1,4 @@
+use std::sync::mpsc::{channel, Receiver};
 use std::time::Instant;
 use std::vec;
 use ggez::{Context, GameError, GameResult};
@@ -7,192 +8,778 @@ use ggez::input::keyboard::KeyCode;
 use ggez::glam::Vec2;
 use ggez::conf::WindowMode;
 use ggez::graphics::{self, DrawMode};
-use ggez::input::keyboard::KeyInput;
-use crate::domain::{Point, Segment, SweepLineProblem, Direction, distance};
-use crate::solvers::{naive_intersection_solver, sweep_line_solver};
+use ggez::input::keyboard::{KeyInput, KeyMods};
+use crate::camera::Camera;
+use crate::incremental::IncrementalSolver;
+use crate::settings::{
+    find_action, load_settings, Action, KeyBinding, Settings, MAX_GRID_SIZE, MIN_GRID_SIZE, SETTINGS_PATH
+};
+use crate::recording::{export_session, save_session, Recording, RecordingOptions, View};
+use crate::hud::{hud_lines, intersection_under, SolveStats};
+use crate::files::{list_problem_files, load_problem, next_free_problem_path, save_problem};
+use crate::history::{remove_command, EditCommand, EditHistory};
+use crate::selection::{delete_command, duplicate_command, move_command, segment_under, segments_in_rectangle};
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem, Direction, distance};
+use crate::solvers::{
+    red_blue_intersection_solver, sweep_line_solver, compare_solvers,
+    SolverComparison, SweepLineStepper, SOLVERS
+};
+use crate::scene::{
+    add_comparison_shapes, add_editing_shapes, add_sweep_shapes, build_scene, Shape, ONLY_A_COLOR, ONLY_B_COLOR,
+    FOUND_BY_BOTH_COLOR, HIGHLIGHT_COLOR
+};
+
+// Directory whose files are cycled through with PageUp and PageDown
+const PROBLEMS_DIRECTORY: &str = "problems";
+// Directory where recorded sessions are written
+const RECORDINGS_DIRECTORY: &str = "recordings";
+// Seconds a status message stays on screen
+const MESSAGE_SECONDS: f64 = 4.0;
+
+// What the left button does
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+enum Tool {
+    // Drags endpoints, or the view on empty space
+    Edit,
+    // Drags a rectangle to select segments, or the selected segments
+    Select,
+    // Every click adds a segment from the previous click
+    Polyline,
+}
+
+// What happened to the segments since they were last solved
+#[derive(Clone)]
+#[derive(PartialEq)]
+enum SegmentsChange {
+    Nothing,
+    // Only the segments at these indices moved
+    Moved(Vec<usize>),
+    // Segments were added or removed, or the result has to be computed again anyway
+    Everything,
+}
+
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+enum PromptKind {
+    Open,
+    SaveAs,
+}
 
 struct MainState {
+    settings: Settings,
+    key_bindings: Vec<(KeyBinding, Action)>,
     sweep_line_problem: SweepLineProblem,
+    // File the segments were read from or last saved to
+    current_path: Option<String>,
+    // Revision of the history that is on disk, the segments are unsaved if it changed
+    saved_revision: u64,
+    window_title: String,
+    // Path being typed in the prompt
+    prompt: Option<(PromptKind, String)>,
+    // The key that opens the prompt also arrives as text, which is not part of the path
+    skip_text_input: bool,
+    message: Option<(String, Instant)>,
+    // Set by the first attempt to leave unsaved segments, the second one discards them
+    discard_warned: bool,
     intersection_alpha: f32,
     init_time: Instant,
     mouse_button: MouseButton,
     mouse_position: Point,
     highlight_point_index: Option<(usize, usize)>,
+    // Index in the result of the intersection within 5 pixels of the mouse
+    hovered_intersection: Option<usize>,
+    show_hud: bool,
+    // What the solver did the last time it ran
+    stats: SolveStats,
+    // The result is only computed again when this is not Nothing
+    change: SegmentsChange,
+    // Incremental mode: moved segments are only tested against the segments near them
+    incremental_mode: bool,
+    incremental: Option<IncrementalSolver>,
+    // Changes of the view since recording started
+    recording: Option<Recording>,
+    // Sessions being rendered on other threads, each one sends the message to show when done
+    exports: Vec<Receiver<String>>,
+    // Minimum distance in pixels between grid lines, the world spacing follows the zoom
     grid_size: f64,
+    camera: Camera,
+    // Dragging with the left button on empty space or with the middle button moves the view
+    panning: bool,
+    history: EditHistory,
+    // Position of the dragged endpoint when the drag started
+    drag_start: Option<Point>,
+    tool: Tool,
+    // Selected segment indices, in increasing order
+    selection: Vec<usize>,
+    // Corner where the selection rectangle being dragged started
+    selection_start: Option<Point>,
+    // Snapped mouse position where moving the selection started, with the selected segments
+    // as they were then
+    moving: Option<(Point, Vec<Segment>)>,
+    // End of the last segment of the polyline being drawn
+    polyline_last: Option<Point>,
+    red_blue_mode: bool,
+    // Step mode: the sweep advances one event at a time instead of being solved every frame
+    stepper: Option<SweepLineStepper>,
+    steps: usize,
+    playing: bool,
+    steps_per_second: f64,
+    last_step_time: Instant,
+    // Comparison mode: indices in SOLVERS of the two solvers run on the segments
+    compared_solvers: Option<(usize, usize)>,
+    comparison: Option<SolverComparison>,
 }
 
 impl MainState {
-    fn new() -> Self {
-        // Initialize your SweepLineProblem here
-        let mut sweep_line_problem = SweepLineProblem::load(
-            "problems/sweep_line_problem_2.txt");
-        MainState {
+    fn new(settings: Settings, path: &str) -> Result<Self, String> {
+        let sweep_line_problem = load_problem(path)?;
+        Ok(MainState {
+            key_bindings: settings.key_bindings()?,
+            grid_size: settings.grid_size,
+            settings,
             sweep_line_problem,
+            current_path: Some(path.to_string()),
+            saved_revision: 0,
+            window_title: String::new(),
+            prompt: None,
+            skip_text_input: false,
+            message: None,
+            discard_warned: false,
             intersection_alpha: 0.0,
             init_time: Instant::now(),
             mouse_button: MouseButton::Other(0),
             mouse_position: Point { x: 0.0, y: 0.0 },
             highlight_point_index: None,
-            grid_size: 10.0,
+            hovered_intersection: None,
+            show_hud: true,
+            stats: SolveStats { solver: String::new(), basic_operations: 0, time: None },
+            change: SegmentsChange::Everything,
+            incremental_mode: false,
+            incremental: None,
+            recording: None,
+            exports: Vec::new(),
+            camera: Camera::default(),
+            panning: false,
+            history: EditHistory::new(),
+            drag_start: None,
+            tool: Tool::Edit,
+            selection: Vec::new(),
+            selection_start: None,
+            moving: None,
+            polyline_last: None,
+            red_blue_mode: false,
+            stepper: None,
+            steps: 0,
+            playing: false,
+            steps_per_second: 4.0,
+            last_step_time: Instant::now(),
+            compared_solvers: None,
+            comparison: None,
+        })
+    }
+
+    fn is_modified(&self) -> bool {
+        self.history.revision() != self.saved_revision
+    }
+
+    fn title(&self) -> String {
+        let name = self.current_path.as_deref()
+            .and_then(|path| std::path::Path::new(path).file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or("untitled");
+        format!("Segments Intersection - {}{}", name, if self.is_modified() { "*" } else { "" })
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+
+    // Whether the segments can be replaced, warning once about unsaved changes
+    fn may_discard(&mut self) -> bool {
+        if !self.is_modified() || self.discard_warned {
+            return true;
+        }
+        self.discard_warned = true;
+        self.show_message("Unsaved changes, do it again to discard them or press S to save".to_string());
+        false
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind, text: String) {
+        self.prompt = Some((kind, text));
+        self.skip_text_input = true;
+    }
+
+    // Replaces the segments with the ones of the file, showing the error if it cannot be read
+    fn open(&mut self, path: &str, width: f64, height: f64) {
+        let problem = match load_problem(path) {
+            Ok(problem) => problem,
+            Err(error) => {
+                self.show_message(error);
+                return;
+            }
+        };
+        self.sweep_line_problem = problem;
+        self.current_path = Some(path.to_string());
+        self.history = EditHistory::new();
+        self.saved_revision = self.history.revision();
+        self.discard_warned = false;
+        self.highlight_point_index = None;
+        self.drag_start = None;
+        self.clear_editing();
+        self.comparison = None;
+        self.playing = false;
+        self.change = SegmentsChange::Everything;
+        if self.stepper.is_some() {
+            self.restart_stepping(0);
+        }
+        // Files in other units, like longitudes and latitudes, would otherwise be out of sight
+        let (x_min, y_min, x_max, y_max) = self.camera.visible(width, height);
+        let visible = self.sweep_line_problem.segments.iter()
+            .flat_map(|segment| [&segment.ini, &segment.end])
+            .any(|p| x_min <= p.x && p.x <= x_max && y_min <= p.y && p.y <= y_max);
+        if !visible {
+            self.camera.fit(&self.sweep_line_problem, width, height);
+        }
+        self.show_message(format!("Opened {}", path));
+    }
+
+    // `with_expected` also writes what the sweep finds as the expected intersections
+    fn save(&mut self, path: &str, with_expected: bool) {
+        match save_problem(&self.sweep_line_problem, path, with_expected) {
+            Ok(()) => {
+                self.current_path = Some(path.to_string());
+                self.saved_revision = self.history.revision();
+                self.discard_warned = false;
+                if with_expected {
+                    self.show_message(format!("Saved {} with the expected intersections", path));
+                } else {
+                    self.show_message(format!("Saved {}", path));
+                }
+            }
+            Err(error) => self.show_message(error),
+        }
+    }
+
+    // Opens the file `offset` places away from the current one in the problems directory
+    fn cycle_file(&mut self, offset: isize, width: f64, height: f64) {
+        let files = list_problem_files(PROBLEMS_DIRECTORY);
+        if files.is_empty() || !self.may_discard() {
+            return;
+        }
+        let current = self.current_path.as_ref().and_then(|current| {
+            files.iter().position(|path| std::path::Path::new(path) == std::path::Path::new(current))
+        });
+        let n = files.len() as isize;
+        let next = match current {
+            Some(i) => (i as isize + offset).rem_euclid(n),
+            None if offset > 0 => 0,
+            None => n - 1,
+        };
+        self.open(&files[next as usize].clone(), width, height);
+    }
+
+    // Enter, Escape and Backspace while a path is being typed
+    fn prompt_key(&mut self, keycode: KeyCode, width: f64, height: f64) {
+        let Some((kind, text)) = &mut self.prompt else { return };
+        match keycode {
+            KeyCode::Back => {
+                text.pop();
+            }
+            KeyCode::Escape => self.prompt = None,
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                let (kind, path) = (*kind, text.trim().to_string());
+                self.prompt = None;
+                if path.is_empty() {
+                    return;
+                }
+                match kind {
+                    PromptKind::Open => {
+                        if self.may_discard() {
+                            self.open(&path, width, height);
+                        }
+                    }
+                    PromptKind::SaveAs => self.save(&path, false),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn mouse_world_position(&self) -> Point {
+        self.camera.screen_to_world(&self.mouse_position)
+    }
+
+    // Snaps a world point to the grid currently drawn, if snapping is on
+    fn snap(&self, p: &Point) -> Point {
+        if !self.settings.snap_to_grid {
+            return p.clone();
+        }
+        p.to_grid(self.camera.grid_size(self.grid_size))
+    }
+
+    // Pick radius in world units
+    fn pick_distance(&self) -> f64 {
+        self.settings.pick_radius / self.camera.scale
+    }
+
+    // Endpoint within the pick radius of the mouse, as (segment index, 0 for ini or 1 for end)
+    fn point_under_mouse(&self) -> Option<(usize, usize)> {
+        let mouse = self.mouse_world_position();
+        for (i, segment) in self.sweep_line_problem.segments.iter().enumerate() {
+            if distance(&mouse, &segment.ini) < self.pick_distance() {
+                return Some((i, 0));
+            }
+            if distance(&mouse, &segment.end) < self.pick_distance() {
+                return Some((i, 1));
+            }
+        }
+        None
+    }
+
+    fn apply_edit(&mut self, command: EditCommand) {
+        self.history.apply(command, &mut self.sweep_line_problem);
+        self.discard_warned = false;
+        self.segments_changed();
+    }
+
+    // Selections and tools in progress refer to indices that edits can change
+    fn clear_editing(&mut self) {
+        self.selection.clear();
+        self.selection_start = None;
+        self.moving = None;
+        self.polyline_last = None;
+    }
+
+    fn set_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        self.clear_editing();
+        let message = match tool {
+            Tool::Edit => "Edit tool: drag endpoints, right click to add or delete",
+            Tool::Select => "Select tool: drag a rectangle or the selection, Delete, Ctrl+D duplicates",
+            Tool::Polyline => "Polyline tool: click to add connected segments, right click or Escape ends",
+        };
+        self.show_message(message.to_string());
+    }
+
+    // Snapped mouse position, or the endpoint under the mouse so that segments connect exactly
+    fn mouse_target(&self) -> Point {
+        match self.highlight_point_index {
+            Some((i, 0)) => self.sweep_line_problem.segments[i].ini.clone(),
+            Some((i, _)) => self.sweep_line_problem.segments[i].end.clone(),
+            None => self.snap(&self.mouse_world_position()),
+        }
+    }
+
+    fn select_down(&mut self, add: bool) {
+        let mouse = self.mouse_world_position();
+        let under = segment_under(&self.sweep_line_problem, &mouse, self.pick_distance());
+        match under {
+            Some(i) => {
+                if !self.selection.contains(&i) {
+                    if !add {
+                        self.selection.clear();
+                    }
+                    self.selection.push(i);
+                    self.selection.sort();
+                }
+                let original = self.selection.iter().map(|&i| self.sweep_line_problem.segments[i].clone()).collect();
+                self.moving = Some((self.snap(&mouse), original));
+            }
+            None => {
+                if !add {
+                    self.selection.clear();
+                }
+                self.selection_start = Some(mouse);
+            }
+        }
+    }
+
+    fn select_up(&mut self) {
+        if let Some((_, original)) = self.moving.take() {
+            let command = move_command(&self.sweep_line_problem, &self.selection, &original);
+            if command != EditCommand::Bulk(Vec::new()) {
+                self.history.record(command);
+                self.discard_warned = false;
+            }
+        }
+        if let Some(start) = self.selection_start.take() {
+            let inside = segments_in_rectangle(&self.sweep_line_problem, &start, &self.mouse_world_position());
+            self.selection.extend(inside);
+            self.selection.sort();
+            self.selection.dedup();
+        }
+    }
+
+    // Follows the mouse with the selected segments, by whole grid steps
+    fn move_selection(&mut self) {
+        let Some((start, original)) = &self.moving else { return };
+        let p = self.snap(&self.mouse_world_position());
+        let (dx, dy) = (p.x - start.x, p.y - start.y);
+        for (&i, segment) in self.selection.iter().zip(original.iter()) {
+            self.sweep_line_problem.segments[i] = Segment {
+                ini: Point { x: segment.ini.x + dx, y: segment.ini.y + dy },
+                end: Point { x: segment.end.x + dx, y: segment.end.y + dy },
+            };
+        }
+        let moved = self.selection.clone();
+        self.segments_moved(&moved);
+    }
+
+    fn polyline_click(&mut self) {
+        let p = self.mouse_target();
+        if let Some(last) = self.polyline_last.take() {
+            if last != p {
+                self.apply_edit(EditCommand::AddSegment {
+                    index: self.sweep_line_problem.segments.len(),
+                    segment: Segment { ini: last, end: p.clone() },
+                    info: SegmentInfo::default(),
+                });
+            }
+        }
+        self.polyline_last = Some(p);
+    }
+
+    fn delete_selection(&mut self) {
+        if !self.selection.is_empty() {
+            let command = delete_command(&self.sweep_line_problem, &self.selection);
+            self.selection.clear();
+            self.highlight_point_index = None;
+            self.apply_edit(command);
+        }
+    }
+
+    // Copies the selection one grid step down and to the right and selects the copies
+    fn duplicate_selection(&mut self) {
+        if !self.selection.is_empty() {
+            let step = self.camera.grid_size(self.grid_size);
+            let n = self.sweep_line_problem.segments.len();
+            let command = duplicate_command(&self.sweep_line_problem, &self.selection, step, step);
+            self.selection = (n..n + self.selection.len()).collect();
+            self.apply_edit(command);
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.history.undo(&mut self.sweep_line_problem) {
+            self.highlight_point_index = None;
+            self.clear_editing();
+            self.segments_changed();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.history.redo(&mut self.sweep_line_problem) {
+            self.highlight_point_index = None;
+            self.clear_editing();
+            self.segments_changed();
+        }
+    }
+
+    // Starts the sweep again and runs it up to the given number of steps
+    fn restart_stepping(&mut self, steps: usize) {
+        self.sweep_line_problem.basic_operations = 0;
+        let mut stepper = SweepLineStepper::new(&mut self.sweep_line_problem);
+        let mut done = 0;
+        while done < steps && stepper.step(&mut self.sweep_line_problem) {
+            done += 1;
+        }
+        self.steps = done;
+        self.stepper = Some(stepper);
+    }
+
+    fn step_forward(&mut self) {
+        if let Some(stepper) = &mut self.stepper {
+            if stepper.step(&mut self.sweep_line_problem) {
+                self.steps += 1;
+            } else {
+                self.playing = false;
+            }
+        }
+    }
+
+    // The treap is not persistent, so going back replays the sweep from the start
+    fn step_back(&mut self) {
+        if self.stepper.is_some() && self.steps > 0 {
+            self.restart_stepping(self.steps - 1);
+        }
+    }
+
+    // Keeps the stepped sweep at the same step after the segments are edited
+    fn segments_changed(&mut self) {
+        self.change = SegmentsChange::Everything;
+        if self.stepper.is_some() {
+            self.restart_stepping(self.steps);
+        }
+    }
+
+    // Same as `segments_changed` when only the segments at the given indices moved
+    fn segments_moved(&mut self, indices: &[usize]) {
+        match &mut self.change {
+            SegmentsChange::Nothing => self.change = SegmentsChange::Moved(indices.to_vec()),
+            SegmentsChange::Moved(moved) => moved.extend_from_slice(indices),
+            SegmentsChange::Everything => (),
+        }
+        if self.stepper.is_some() {
+            self.restart_stepping(self.steps);
+        }
+    }
+
+    // Writes the recorded session under the first free recordings/session_N name, then
+    // renders it to an animated GIF, or to a directory of PNG frames, on another thread
+    fn stop_recording(&mut self, as_gif: bool) {
+        let Some(session) = self.recording.take().and_then(|recording| recording.finish()) else { return };
+        let mut i = 0;
+        while std::path::Path::new(&format!("{}/session_{}.jsonl", RECORDINGS_DIRECTORY, i)).exists() {
+            i += 1;
+        }
+        let base = format!("{}/session_{}", RECORDINGS_DIRECTORY, i);
+        let output = if as_gif { format!("{}.gif", base) } else { base.clone() };
+        let saved = std::fs::create_dir_all(RECORDINGS_DIRECTORY)
+            .map_err(|error| error.to_string())
+            .and_then(|_| save_session(&session, &format!("{}.jsonl", base)));
+        if let Err(error) = saved {
+            self.show_message(error);
+            return;
+        }
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            let message = match export_session(&session, &RecordingOptions::default(), &output) {
+                Ok(n_frames) => format!("Recorded {} frames to {}", n_frames, output),
+                Err(error) => error,
+            };
+            let _ = sender.send(message);
+        });
+        self.exports.push(receiver);
+        self.show_message(format!("Rendering {}.jsonl", base));
+    }
+
+    // Shows the messages of the sessions that finished rendering
+    fn poll_exports(&mut self) {
+        let mut messages = Vec::new();
+        self.exports.retain(|receiver| match receiver.try_recv() {
+            Ok(message) => {
+                messages.push(message);
+                false
+            }
+            Err(error) => error == std::sync::mpsc::TryRecvError::Empty,
+        });
+        for message in messages {
+            self.show_message(message);
+        }
+    }
+
+    // Runs the solver of the current mode if the segments changed
+    fn solve(&mut self) {
+        let change = std::mem::replace(&mut self.change, SegmentsChange::Nothing);
+        if change == SegmentsChange::Nothing {
+            return;
+        }
+        if let Some((a, b)) = self.compared_solvers {
+            let comparison = compare_solvers(&self.sweep_line_problem, a, b);
+            self.stats = SolveStats {
+                solver: format!("{} and {}", comparison.a.name, comparison.b.name),
+                basic_operations: comparison.a.basic_operations + comparison.b.basic_operations,
+                time: Some(comparison.a.time + comparison.b.time),
+            };
+            self.comparison = Some(comparison);
+            self.sweep_line_problem.result.clear();
+            self.sweep_line_problem.result_pairs.clear();
+            return;
         }
+        let start = Instant::now();
+        let name = if self.incremental_mode {
+            match (&mut self.incremental, change) {
+                (Some(incremental), SegmentsChange::Moved(moved)) => {
+                    incremental.update_segments(&mut self.sweep_line_problem, &moved);
+                }
+                _ => self.incremental = Some(IncrementalSolver::new(&mut self.sweep_line_problem, self.red_blue_mode)),
+            }
+            if self.red_blue_mode { "incremental red-blue" } else { "incremental" }
+        } else if self.red_blue_mode {
+            self.sweep_line_problem.basic_operations = 0;
+            red_blue_intersection_solver(&mut self.sweep_line_problem);
+            "red-blue"
+        } else {
+            self.sweep_line_problem.basic_operations = 0;
+            sweep_line_solver(&mut self.sweep_line_problem);
+            "sweep line"
+        };
+        self.stats = SolveStats {
+            solver: name.to_string(),
+            basic_operations: self.sweep_line_problem.basic_operations,
+            time: Some(start.elapsed().as_secs_f64()),
+        };
     }
 }
 
 impl EventHandler for MainState {
     fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        self.skip_text_input = false;
+        let title = self.title();
+        if title != self.window_title {
+            _ctx.gfx.set_window_title(&title);
+            self.window_title = title;
+        }
+        if self.message.as_ref().is_some_and(|(_, time)| time.elapsed().as_secs_f64() > MESSAGE_SECONDS) {
+            self.message = None;
+        }
+        self.poll_exports();
         self.intersection_alpha = (((self.init_time.elapsed().as_secs_f64()*2.0).sin() + 1.0) / 2.0 + 0.1) as f32;
-        sweep_line_solver(&mut self.sweep_line_problem);
+        if self.stepper.is_some() {
+            if self.playing && self.last_step_time.elapsed().as_secs_f64() >= 1.0 / self.steps_per_second {
+                self.step_forward();
+                self.last_step_time = Instant::now();
+            }
+            let done = if self.stepper.as_ref().is_some_and(|stepper| stepper.is_done()) { ", done" } else { "" };
+            self.stats = SolveStats {
+                solver: format!("sweep line, step {}{}", self.steps, done),
+                basic_operations: self.sweep_line_problem.basic_operations,
+                time: None,
+            };
+        } else {
+            self.solve();
+        }
         //naive_intersection_solver(&mut self.sweep_line_problem);
         if self.mouse_button != MouseButton::Left {
-            self.highlight_point_index = None;
-            for i in 0..self.sweep_line_problem.segments.len() {
-                let segment = &self.sweep_line_problem.segments[i];
-                if distance(&self.mouse_position, &segment.ini) < 5.0 {
-                    self.highlight_point_index = Some((i, 0));
-                    break;
-                }
-                if distance(&self.mouse_position, &segment.end) < 5.0 {
-                    self.highlight_point_index = Some((i, 1));
-                    break;
-                }
-            }
+            self.highlight_point_index = self.point_under_mouse();
         }
+        self.hovered_intersection = intersection_under(
+            &self.sweep_line_problem, &self.mouse_world_position(), self.pick_distance()
+        );
         Ok(())
     }
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let mut canvas =
-            graphics::Canvas::from_frame(ctx, graphics::Color::from([0.1, 0.2, 0.3, 1.0]));
-
         // Get the window size
         let (width, height) = ctx.gfx.size();
-
-        //Draw grid
-        for i in 0..(width / self.grid_size as f32) as i32 {
-            canvas.draw(
-                &graphics::Mesh::new_line(
-                    ctx,
-                    &[
-                        Vec2::new((i as f64 * self.grid_size) as f32, 0.0),
-                        Vec2::new((i as f64 * self.grid_size) as f32, height)
-                    ],
-                    1.0,
-                    graphics::Color::from([0.5, 0.5, 0.5, 0.2]),
-                )?,
-                graphics::DrawParam::default(),
-            );
+        let mut scene = build_scene(
+            &self.sweep_line_problem, width, height, &self.camera, self.camera.grid_size(self.grid_size),
+            self.intersection_alpha, self.highlight_point_index, &self.settings.theme
+        );
+        if let Some(stepper) = &self.stepper {
+            add_sweep_shapes(&mut scene, &self.sweep_line_problem, stepper);
         }
-        for i in 0..(height / self.grid_size as f32) as i32 {
-            canvas.draw(
-                &graphics::Mesh::new_line(
-                    ctx,
-                    &[
-                        Vec2::new(0.0, (i as f64 * self.grid_size) as f32),
-                        Vec2::new(width, (i as f64 * self.grid_size) as f32),
-                    ],
-                    1.0,
-                    graphics::Color::from([0.5, 0.5, 0.5, 0.2]),
-                )?,
-                graphics::DrawParam::default(),
-            );
+        if let Some(k) = self.hovered_intersection {
+            let intersection = &self.sweep_line_problem.result[k];
+            for center in [&intersection.ini, &intersection.end] {
+                scene.shapes.push(Shape::Circle { center: center.clone(), radius: 7.0, color: HIGHLIGHT_COLOR });
+            }
+        }
+        let rectangle = self.selection_start.clone().map(|start| (start, self.mouse_world_position()));
+        let polyline_preview = self.polyline_last.clone().map(|last| (last, self.mouse_target()));
+        add_editing_shapes(&mut scene, &self.sweep_line_problem, &self.selection, rectangle, polyline_preview);
+        let comparison = self.comparison.as_ref().filter(|_| self.compared_solvers.is_some());
+        if let Some(comparison) = comparison {
+            add_comparison_shapes(&mut scene, comparison, self.intersection_alpha);
+        }
+        if let Some(recording) = &mut self.recording {
+            let view = View {
+                width,
+                height,
+                camera: self.camera.clone(),
+                grid_size: self.grid_size,
+                red_blue: self.red_blue_mode,
+                steps: self.stepper.as_ref().map(|_| self.steps),
+                compared_solvers: self.compared_solvers,
+            };
+            recording.capture(&view, &self.sweep_line_problem, &self.selection);
         }
 
-        // Draw segments
-        for segment in &self.sweep_line_problem.segments {
-            canvas.draw(
-                &graphics::Mesh::new_line(
+        let mut canvas =
+            graphics::Canvas::from_frame(ctx, graphics::Color::from(scene.background));
+        let to_screen = |p: &Point| {
+            let p = scene.camera.world_to_screen(p);
+            Vec2::new(p.x as f32, p.y as f32)
+        };
+        for shape in &scene.shapes {
+            let mesh = match shape {
+                Shape::Line { from, to, width, color } => graphics::Mesh::new_line(
                     ctx,
-                    &[
-                        Vec2::new(segment.ini.x as f32, segment.ini.y as f32),
-                        Vec2::new(segment.end.x as f32, segment.end.y as f32),
-                    ],
-                    2.0,
-                    graphics::Color::from([1.0, 1.0, 1.0, 1.0]),
+                    &[to_screen(from), to_screen(to)],
+                    *width,
+                    graphics::Color::from(*color),
                 )?,
-                graphics::DrawParam::default(),
-            );
-
-            canvas.draw(
-                &graphics::Mesh::new_circle(
+                Shape::Circle { center, radius, color } => graphics::Mesh::new_circle(
                     ctx,
                     DrawMode::fill(),
-                    Vec2::new(segment.ini.x as f32, segment.ini.y as f32),
-                    2.0,
+                    to_screen(center),
+                    *radius,
                     0.1,
-                    graphics::Color::from([1.0, 1.0, 1.0, 1.0]),
+                    graphics::Color::from(*color),
                 )?,
-                graphics::DrawParam::default(),
-            );
+            };
+            canvas.draw(&mesh, graphics::DrawParam::default());
+        }
 
-            canvas.draw(
-                &graphics::Mesh::new_circle(
-                    ctx,
-                    DrawMode::fill(),
-                    Vec2::new(segment.end.x as f32, segment.end.y as f32),
-                    2.0,
-                    0.1,
-                    graphics::Color::from([1.0, 1.0, 1.0, 1.0]),
-                )?,
-                graphics::DrawParam::default(),
-            );
+        let mut top_lines = 0;
+        if let Some(comparison) = comparison {
+            top_lines = 3;
+            let lines = [
+                (format!(
+                    "A {}: {} found, {} basic operations, {:.3} ms, {} only by A",
+                    comparison.a.name, comparison.a.found, comparison.a.basic_operations,
+                    comparison.a.time * 1000.0, comparison.only_a.len()
+                ), ONLY_A_COLOR),
+                (format!(
+                    "B {}: {} found, {} basic operations, {:.3} ms, {} only by B",
+                    comparison.b.name, comparison.b.found, comparison.b.basic_operations,
+                    comparison.b.time * 1000.0, comparison.only_b.len()
+                ), ONLY_B_COLOR),
+                (format!("{} found by both", comparison.both.len()), FOUND_BY_BOTH_COLOR),
+            ];
+            for (k, (line, [r, g, b])) in lines.into_iter().enumerate() {
+                canvas.draw(
+                    &graphics::Text::new(line),
+                    graphics::DrawParam::default()
+                        .dest(Vec2::new(10.0, 10.0 + 20.0 * k as f32))
+                        .color(graphics::Color::new(r, g, b, 1.0)),
+                );
+            }
         }
-        for segment in &self.sweep_line_problem.result {
-            canvas.draw(
-                &graphics::Mesh::new_line(
-                    ctx,
-                    &[
-                        Vec2::new(segment.ini.x as f32, segment.ini.y as f32),
-                        Vec2::new(segment.end.x as f32, segment.end.y as f32),
-                    ],
-                    5.0,
-                    graphics::Color::from([1.0, 0.0, 0.0, self.intersection_alpha]),
-                )?,
-                graphics::DrawParam::default(),
+
+        if self.show_hud {
+            let lines = hud_lines(
+                &self.sweep_line_problem, &self.stats, &self.mouse_world_position(),
+                self.highlight_point_index, self.hovered_intersection,
             );
+            for (k, line) in lines.into_iter().enumerate() {
+                canvas.draw(
+                    &graphics::Text::new(line),
+                    graphics::DrawParam::default()
+                        .dest(Vec2::new(10.0, 10.0 + 20.0 * (top_lines + k) as f32))
+                        .color(graphics::Color::WHITE),
+                );
+            }
+        }
 
+        if self.recording.is_some() {
             canvas.draw(
-                &graphics::Mesh::new_circle(
-                    ctx,
-                    DrawMode::fill(),
-                    Vec2::new(segment.ini.x as f32, segment.ini.y as f32),
-                    5.0,
-                    0.1,
-                    graphics::Color::from([1.0, 0.0, 0.0, self.intersection_alpha]),
-                )?,
-                graphics::DrawParam::default(),
+                &graphics::Text::new("REC"),
+                graphics::DrawParam::default()
+                    .dest(Vec2::new(width - 50.0, 10.0))
+                    .color(graphics::Color::RED),
             );
+        }
 
+        // Prompt and status message along the bottom of the window
+        let mut bottom_lines = Vec::new();
+        if let Some((message, _)) = &self.message {
+            bottom_lines.push(message.clone());
+        }
+        if let Some((kind, text)) = &self.prompt {
+            let label = match kind {
+                PromptKind::Open => "Open",
+                PromptKind::SaveAs => "Save as",
+            };
+            bottom_lines.push(format!("{}: {}_", label, text));
+        }
+        for (k, line) in bottom_lines.iter().rev().enumerate() {
             canvas.draw(
-                &graphics::Mesh::new_circle(
-                    ctx,
-                    DrawMode::fill(),
-                    Vec2::new(segment.end.x as f32, segment.end.y as f32),
-                    5.0,
-                    0.1,
-                    graphics::Color::from([1.0, 0.0, 0.0, self.intersection_alpha]),
-                )?,
-                graphics::DrawParam::default(),
+                &graphics::Text::new(line.as_str()),
+                graphics::DrawParam::default()
+                    .dest(Vec2::new(10.0, height - 30.0 - 20.0 * k as f32))
+                    .color(graphics::Color::WHITE),
             );
-
-            if self.highlight_point_index.is_some() {
-                let segment = &self.sweep_line_problem.segments[self.highlight_point_index.unwrap().0];
-                let point = if self.highlight_point_index.unwrap().1 == 0 {
-                    &segment.ini
-                } else {
-                    &segment.end
-                };
-                canvas.draw(
-                    &graphics::Mesh::new_circle(
-                        ctx,
-                        DrawMode::fill(),
-                        Vec2::new(point.x as f32, point.y as f32),
-                        5.0,
-                        0.1,
-                        graphics::Color::from([0.0, 0.0, 1.0, 1.0]),
-                    )?,
-                    graphics::DrawParam::default(),
-                );
-            }
-
         }
 
         canvas.finish(ctx)?;
@@ -200,85 +787,264 @@ impl EventHandler for MainState {
     }
 
     fn mouse_button_down_event(&mut self, _ctx: &mut Context, _button: MouseButton, _x: f32, _y: f32) -> Result<(), GameError> {
-        if _button == MouseButton::Left {
+        if _button == MouseButton::Left && self.tool == Tool::Select {
             self.mouse_button = _button;
+            self.select_down(_ctx.keyboard.is_mod_active(KeyMods::SHIFT));
+        }
+        else if _button == MouseButton::Left && self.tool == Tool::Polyline {
+            self.polyline_click();
+        }
+        else if _button == MouseButton::Right && self.tool == Tool::Polyline {
+            self.polyline_last = None;
         }
-        else if _button == MouseButton::Right {
-            let mut i_to_delete = -1;
-            for i in 0..self.sweep_line_problem.segments.len() {
+        else if _button == MouseButton::Left {
+            self.mouse_button = _button;
+            self.panning = self.highlight_point_index.is_none();
+            if let Some((i, j)) = self.highlight_point_index {
                 let segment = &self.sweep_line_problem.segments[i];
-                if distance(&self.mouse_position, &segment.ini) < 5.0
-                    || distance(&self.mouse_position, &segment.end) < 5.0 {
-                    i_to_delete = i as i32;
-                    break;
-                }
+                self.drag_start = Some(if j == 0 { segment.ini.clone() } else { segment.end.clone() });
             }
-            if i_to_delete != -1 {
-                self.sweep_line_problem.segments.remove(i_to_delete as usize);
+        }
+        else if _button == MouseButton::Middle {
+            self.panning = true;
+        }
+        // A right click during a left drag is ignored, removing the dragged segment would
+        // leave the drag on another one
+        else if _button == MouseButton::Right && self.mouse_button != MouseButton::Left {
+            self.selection.clear();
+            if let Some((i_to_delete, _)) = self.point_under_mouse() {
+                self.apply_edit(remove_command(&self.sweep_line_problem, i_to_delete));
             }
             else {
-                self.sweep_line_problem.segments.push(
-                    Segment {
-                        ini: Point { x: _x as f64, y: _y as f64 }.to_grid(self.grid_size),
-                        end: Point { x: _x as f64, y: _y as f64 }.to_grid(self.grid_size),
-                    }
-                );
+                let p = self.snap(&self.mouse_world_position());
+                self.apply_edit(EditCommand::AddSegment {
+                    index: self.sweep_line_problem.segments.len(),
+                    segment: Segment { ini: p.clone(), end: p },
+                    info: SegmentInfo::default(),
+                });
             }
         }
         Ok(())
     }
 
     fn mouse_button_up_event(&mut self, _ctx: &mut Context, _button: MouseButton, _x: f32, _y: f32) -> Result<(), GameError> {
-        if _button == MouseButton::Left {
+        if _button == MouseButton::Left && self.tool == Tool::Select {
             self.mouse_button = MouseButton::Other(0);
+            self.select_up();
+        }
+        else if _button == MouseButton::Left {
+            self.mouse_button = MouseButton::Other(0);
+            // The whole drag is a single edit
+            if let (Some(from), Some((i, j))) = (self.drag_start.take(), self.highlight_point_index) {
+                let segment = &self.sweep_line_problem.segments[i];
+                let to = if j == 0 { segment.ini.clone() } else { segment.end.clone() };
+                if to != from {
+                    self.history.record(EditCommand::MoveEndpoint { index: i, end: j, from, to });
+                }
+            }
+        }
+        if _button == MouseButton::Left || _button == MouseButton::Middle {
+            self.panning = false;
         }
         Ok(())
     }
 
     fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) -> Result<(), GameError> {
         self.mouse_position = Point { x: x as f64, y: y as f64 };
-        if self.mouse_button == MouseButton::Left {
+        if self.panning {
+            self.camera.pan(_dx as f64, _dy as f64);
+        }
+        else if self.moving.is_some() {
+            self.move_selection();
+        }
+        else if self.mouse_button == MouseButton::Left && self.tool == Tool::Edit {
             if let Some((i, j)) = self.highlight_point_index {
+                let p = self.snap(&self.mouse_world_position());
                 if j == 0 {
-                    self.sweep_line_problem.segments[i].ini = Point { x: x as f64, y: y as f64 }.to_grid(self.grid_size);
+                    self.sweep_line_problem.segments[i].ini = p;
                 } else {
-                    self.sweep_line_problem.segments[i].end = Point { x: x as f64, y: y as f64 }.to_grid(self.grid_size);
+                    self.sweep_line_problem.segments[i].end = p;
                 }
+                self.segments_moved(&[i]);
+            }
+        }
+        Ok(())
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) -> Result<(), GameError> {
+        self.camera.zoom_at(&self.mouse_position, 1.1f64.powf(y as f64));
+        Ok(())
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> Result<(), GameError> {
+        if self.skip_text_input {
+            self.skip_text_input = false;
+            return Ok(());
+        }
+        if let Some((_, text)) = &mut self.prompt {
+            if !character.is_control() {
+                text.push(character);
             }
         }
         Ok(())
     }
 
     fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeated: bool) -> Result<(), GameError> {
-        match input.keycode {
-            Some(KeyCode::S) => {
-                let mut i = 0;
-                loop {
-                    let file_name = format!("problems/sweep_line_problem_{}.txt", i);
-                    if std::path::Path::new(&file_name).exists() {
-                        i += 1;
-                    } else {
-                        self.sweep_line_problem.save(&file_name);
-                        break;
-                    }
+        let (width, height) = ctx.gfx.size();
+        let (width, height) = (width as f64, height as f64);
+        // Keys are typed into the prompt while it is open
+        if self.prompt.is_some() {
+            if let Some(keycode) = input.keycode {
+                self.prompt_key(keycode, width, height);
+            }
+            return Ok(());
+        }
+        let Some(keycode) = input.keycode else { return Ok(()) };
+        let Some(action) = find_action(&self.key_bindings, keycode, input.mods) else { return Ok(()) };
+        match action {
+            Action::SaveAs => {
+                let path = self.current_path.clone().unwrap_or_else(next_free_problem_path);
+                self.open_prompt(PromptKind::SaveAs, path);
+            }
+            Action::Save => match self.current_path.clone() {
+                Some(path) => self.save(&path, false),
+                None => self.open_prompt(PromptKind::SaveAs, next_free_problem_path()),
+            },
+            Action::SaveExpected => match self.current_path.clone() {
+                Some(path) => self.save(&path, true),
+                None => self.show_message("Save the problem first".to_string()),
+            },
+            Action::Open => {
+                let path = self.current_path.as_deref()
+                    .and_then(|path| std::path::Path::new(path).parent())
+                    .and_then(|directory| directory.to_str())
+                    .filter(|directory| !directory.is_empty())
+                    .map_or(String::new(), |directory| format!("{}/", directory));
+                self.open_prompt(PromptKind::Open, path);
+            }
+            Action::NextFile => self.cycle_file(1, width, height),
+            Action::PreviousFile => self.cycle_file(-1, width, height),
+            Action::Undo => self.undo(),
+            Action::Duplicate => self.duplicate_selection(),
+            Action::SelectAll => {
+                if self.tool != Tool::Select {
+                    self.set_tool(Tool::Select);
                 }
+                self.selection = (0..self.sweep_line_problem.segments.len()).collect();
+            }
+            Action::Hud => self.show_hud = !self.show_hud,
+            Action::Record | Action::RecordFrames => {
+                if self.recording.is_some() {
+                    self.stop_recording(action == Action::Record);
+                } else {
+                    self.recording = Some(Recording::new(&self.settings.theme));
+                }
+            }
+            Action::EditTool => self.set_tool(Tool::Edit),
+            Action::SelectTool => self.set_tool(Tool::Select),
+            Action::PolylineTool => self.set_tool(Tool::Polyline),
+            Action::Delete => self.delete_selection(),
+            Action::Cancel => {
+                self.polyline_last = None;
+                self.selection.clear();
+            }
+            Action::Redo => self.redo(),
+            Action::ToggleColor => {
+                if let Some((i, _)) = self.highlight_point_index {
+                    let from = self.sweep_line_problem.segment_info[i].color;
+                    let to = match from {
+                        SegmentColor::Red => SegmentColor::Blue,
+                        SegmentColor::Blue => SegmentColor::Red,
+                    };
+                    self.apply_edit(EditCommand::SetColor { index: i, from, to });
+                }
+            }
+            Action::RedBlue => {
+                self.red_blue_mode = !self.red_blue_mode;
+                // The incremental solver knows the mode it was built for
+                self.incremental = None;
+                self.change = SegmentsChange::Everything;
+            }
+            Action::Incremental => {
+                self.incremental_mode = !self.incremental_mode;
+                self.incremental = None;
+                self.change = SegmentsChange::Everything;
+            }
+            Action::StepMode => {
+                if self.stepper.is_some() {
+                    self.stepper = None;
+                } else {
+                    self.compared_solvers = None;
+                    self.restart_stepping(0);
+                }
+                self.playing = false;
+                self.change = SegmentsChange::Everything;
+            }
+            Action::Fit => self.camera.fit(&self.sweep_line_problem, width, height),
+            Action::Compare => {
+                if self.compared_solvers.is_some() {
+                    self.compared_solvers = None;
+                } else {
+                    // Naive against sweep line to start with
+                    self.stepper = None;
+                    self.compared_solvers = Some((0, 1));
+                }
+                self.change = SegmentsChange::Everything;
+            }
+            Action::NextSolverA => {
+                if let Some((a, b)) = self.compared_solvers {
+                    self.compared_solvers = Some(((a + 1) % SOLVERS.len(), b));
+                    self.change = SegmentsChange::Everything;
+                }
+            }
+            Action::NextSolverB => {
+                if let Some((a, b)) = self.compared_solvers {
+                    self.compared_solvers = Some((a, (b + 1) % SOLVERS.len()));
+                    self.change = SegmentsChange::Everything;
+                }
+            }
+            Action::StepForward => self.step_forward(),
+            Action::StepBack => self.step_back(),
+            Action::Play => {
+                self.playing = !self.playing;
+                self.last_step_time = Instant::now();
+            }
+            Action::Faster => self.steps_per_second = f64::min(self.steps_per_second * 2.0, 256.0),
+            Action::Slower => self.steps_per_second = f64::max(self.steps_per_second / 2.0, 0.25),
+            // The drawn spacing is 1, 2 or 5 times a power of ten, so halving the minimum
+            // moves it one of those steps
+            Action::FinerGrid => {
+                self.grid_size = f64::max(self.grid_size / 2.0, MIN_GRID_SIZE);
+                self.show_message(format!("Grid every {} units", self.camera.grid_size(self.grid_size)));
+            }
+            Action::CoarserGrid => {
+                self.grid_size = f64::min(self.grid_size * 2.0, MAX_GRID_SIZE);
+                self.show_message(format!("Grid every {} units", self.camera.grid_size(self.grid_size)));
+            }
+            Action::Snap => {
+                self.settings.snap_to_grid = !self.settings.snap_to_grid;
+                let state = if self.settings.snap_to_grid { "on" } else { "off" };
+                self.show_message(format!("Snapping to the grid {}", state));
             }
-            _ => (),
         }
         Ok(())
     }
 }
 
-pub fn visualization() -> GameResult<()> {
+// Opens the problem file at the path, or the default one of the settings
+pub fn visualization(path: Option<String>) -> GameResult<()> {
+    let settings = load_settings(SETTINGS_PATH).map_err(GameError::CustomError)?;
+    let path = path.unwrap_or_else(|| settings.default_problem.clone());
+    let (width, height) = (settings.window_width, settings.window_height);
+    let state = MainState::new(settings, &path).map_err(GameError::CustomError)?;
     let (mut ctx, event_loop) =
         ggez::ContextBuilder::new("segments", "your_name").build()?;
     ctx.gfx.set_window_title("Segments Intersection");
     ctx.gfx.set_mode(WindowMode {
-        width: 800.0,
-        height: 600.0,
+        width,
+        height,
         resizable: true,
         ..Default::default()
     }).expect("Error setting window mode");
-    let state = MainState::new();
     event::run(ctx, event_loop, state)
 }
\ No newline at end of file
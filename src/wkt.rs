@@ -0,0 +1,297 @@
+use std::fmt;
+use crate::domain::{Point, Segment, SegmentColor, SegmentInfo, SweepLineProblem};
+use crate::solvers::naive_intersection_solver;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct WktError {
+    // Byte offset in the input, and the same place as 1-based line and column
+    pub position: usize,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for WktError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: String) -> WktError {
+        let before = &self.text[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        WktError { position: self.position, line, column, message }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.position..].chars().next()
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), WktError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += c.len_utf8();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found the end of the input", expected))),
+        }
+    }
+
+    // Consumes the character if it is the next one
+    fn accept(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    fn word(&mut self) -> String {
+        self.skip_whitespace();
+        let rest = &self.text[self.position..];
+        let length = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        self.position += length;
+        rest[..length].to_ascii_uppercase()
+    }
+
+    fn number(&mut self) -> Result<f64, WktError> {
+        self.skip_whitespace();
+        let rest = &self.text[self.position..];
+        let length = rest.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(rest.len());
+        match rest[..length].parse::<f64>() {
+            Ok(value) if length > 0 => {
+                self.position += length;
+                Ok(value)
+            }
+            _ => Err(self.error(format!("expected a number, found '{}'",
+                rest.split(|c: char| c.is_whitespace() || c == ',' || c == ')').next().unwrap_or(""))
+            )),
+        }
+    }
+
+    // x y followed by the Z and M values, which are dropped
+    fn point(&mut self) -> Result<Point, WktError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.') {
+            self.number()?;
+        }
+        Ok(Point { x, y })
+    }
+
+    // Z, M or ZM after the geometry name, and the EMPTY keyword. Returns false for EMPTY.
+    fn dimensions_and_empty(&mut self) -> Result<bool, WktError> {
+        let start = self.position;
+        let mut word = self.word();
+        if word == "Z" || word == "M" || word == "ZM" {
+            word = self.word();
+        }
+        match word.as_str() {
+            "EMPTY" => Ok(false),
+            "" => Ok(true),
+            _ => {
+                self.position = start;
+                Err(self.error(format!("unexpected '{}'", word)))
+            }
+        }
+    }
+
+    fn point_list(&mut self) -> Result<Vec<Point>, WktError> {
+        self.expect('(')?;
+        let mut points = vec![self.point()?];
+        while self.accept(',') {
+            points.push(self.point()?);
+        }
+        self.expect(')')?;
+        Ok(points)
+    }
+
+    fn point_list_list(&mut self) -> Result<Vec<Vec<Point>>, WktError> {
+        self.expect('(')?;
+        let mut lists = vec![self.point_list()?];
+        while self.accept(',') {
+            lists.push(self.point_list()?);
+        }
+        self.expect(')')?;
+        Ok(lists)
+    }
+
+    // Lines of the next geometry, polygon rings are closed if they are not already
+    fn geometry(&mut self) -> Result<(String, Vec<Vec<Point>>), WktError> {
+        let start = self.position;
+        let name = self.word();
+        let lines = match name.as_str() {
+            "LINESTRING" => {
+                if !self.dimensions_and_empty()? { return Ok((name, Vec::new())); }
+                vec![self.point_list()?]
+            }
+            "MULTILINESTRING" => {
+                if !self.dimensions_and_empty()? { return Ok((name, Vec::new())); }
+                self.point_list_list()?
+            }
+            "POLYGON" => {
+                if !self.dimensions_and_empty()? { return Ok((name, Vec::new())); }
+                close_rings(self.point_list_list()?)
+            }
+            "MULTIPOLYGON" => {
+                if !self.dimensions_and_empty()? { return Ok((name, Vec::new())); }
+                let mut rings = Vec::new();
+                self.expect('(')?;
+                rings.extend(close_rings(self.point_list_list()?));
+                while self.accept(',') {
+                    rings.extend(close_rings(self.point_list_list()?));
+                }
+                self.expect(')')?;
+                rings
+            }
+            "GEOMETRYCOLLECTION" => {
+                if !self.dimensions_and_empty()? { return Ok((name, Vec::new())); }
+                let mut lines = Vec::new();
+                self.expect('(')?;
+                lines.extend(self.geometry()?.1);
+                while self.accept(',') {
+                    lines.extend(self.geometry()?.1);
+                }
+                self.expect(')')?;
+                lines
+            }
+            "" => {
+                return Err(self.error(format!("expected a geometry, found '{}'",
+                    self.text[self.position..].chars().next().unwrap_or(' '))));
+            }
+            _ => {
+                self.position = start;
+                return Err(self.error(format!("unsupported geometry type '{}'", name)));
+            }
+        };
+        Ok((name, lines))
+    }
+}
+
+fn close_rings(mut rings: Vec<Vec<Point>>) -> Vec<Vec<Point>> {
+    for ring in rings.iter_mut() {
+        if ring.len() > 1 && ring.first() != ring.last() {
+            ring.push(ring[0].clone());
+        }
+    }
+    rings
+}
+
+// Reads one or more geometries separated by whitespace or ';', as in a PostGIS dump, with
+// an optional EWKT "SRID=...;" prefix. Lines and polygon rings are split into segments,
+// labelled with the index of their geometry and tagged with its type.
+pub fn parse_wkt(text: &str) -> Result<SweepLineProblem, WktError> {
+    let mut parser = Parser { text, position: 0 };
+    let mut problem = SweepLineProblem::new(Vec::new());
+    let mut index = 0;
+    while !parser.at_end() {
+        let prefix = parser.text.get(parser.position..parser.position + 5);
+        if prefix.is_some_and(|prefix| prefix.eq_ignore_ascii_case("SRID=")) {
+            match parser.text[parser.position..].find(';') {
+                Some(end) => parser.position += end + 1,
+                None => return Err(parser.error("SRID without ';'".to_string())),
+            }
+        }
+        let (name, lines) = parser.geometry()?;
+        for line in lines {
+            for pair in line.windows(2) {
+                problem.add_segment(
+                    Segment { ini: pair[0].clone(), end: pair[1].clone() },
+                    SegmentInfo::new(SegmentColor::Red, index.to_string(), vec![name.to_ascii_lowercase()])
+                );
+            }
+        }
+        index += 1;
+        parser.accept(';');
+    }
+    Ok(problem)
+}
+
+pub fn load_wkt(path: &str) -> Result<SweepLineProblem, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse_wkt(&text).map_err(|error| format!("{}: {}", path, error))
+}
+
+fn format_segments<'a>(segments: impl Iterator<Item = &'a Segment>) -> String {
+    let lines: Vec<String> = segments
+        .map(|s| format!("({} {}, {} {})", s.ini.x, s.ini.y, s.end.x, s.end.y))
+        .collect();
+    if lines.is_empty() {
+        return "MULTILINESTRING EMPTY".to_string();
+    }
+    format!("MULTILINESTRING ({})", lines.join(", "))
+}
+
+// The input segments as a single MULTILINESTRING
+pub fn segments_to_wkt(problem: &SweepLineProblem) -> String {
+    format_segments(problem.segments.iter())
+}
+
+// Intersection points as a MULTIPOINT
+pub fn intersection_points_to_wkt(problem: &SweepLineProblem) -> String {
+    let points: Vec<String> = problem.result.iter()
+        .filter(|intersection| intersection.ini == intersection.end)
+        .map(|intersection| format!("({} {})", intersection.ini.x, intersection.ini.y))
+        .collect();
+    if points.is_empty() {
+        return "MULTIPOINT EMPTY".to_string();
+    }
+    format!("MULTIPOINT ({})", points.join(", "))
+}
+
+// Overlaps between collinear segments as a MULTILINESTRING
+pub fn overlaps_to_wkt(problem: &SweepLineProblem) -> String {
+    format_segments(problem.result.iter().filter(|intersection| intersection.ini != intersection.end))
+}
+
+pub fn test_wkt() {
+    let text = "SRID=4326;LINESTRING (0 0, 10 10, 20 0);\n\
+                multilinestring((0 5, 20 5), (0 0, 5 5))\n\
+                POLYGON Z ((30 0 1, 40 0 1, 40 10 1, 30 10 1))\n\
+                LINESTRING EMPTY";
+    let mut problem = parse_wkt(text).unwrap();
+    assert_eq!(problem.segments.len(), 2 + 2 + 4);
+    assert_eq!(problem.segment_info[2].label(), "1");
+    assert_eq!(problem.segment_info[7].tags(), ["polygon".to_string()]);
+    // The polygon ring is closed back to its first point
+    assert_eq!(problem.segments[7].end, Point { x: 30.0, y: 0.0 });
+
+    naive_intersection_solver(&mut problem);
+    assert!(intersection_points_to_wkt(&problem).starts_with("MULTIPOINT ("));
+    assert!(intersection_points_to_wkt(&problem).contains("(5 5)"));
+    assert_eq!(overlaps_to_wkt(&problem), "MULTILINESTRING ((0 0, 5 5))");
+    let reloaded = parse_wkt(&segments_to_wkt(&problem)).unwrap();
+    assert!(reloaded.segments == problem.segments);
+
+    let Err(error) = parse_wkt("LINESTRING (0 0, 1 1)\nLINESTRING (0 0, 1 x)") else { panic!() };
+    assert_eq!((error.line, error.column), (2, 20));
+    assert_eq!(error.position, 41);
+    let Err(error) = parse_wkt("POINT (1 2)") else { panic!() };
+    assert_eq!((error.line, error.column), (1, 1));
+    assert!(parse_wkt("LINESTRING (0 0, 1 1").is_err());
+    // The SRID prefix in any case, and text too short or not ASCII where it could start
+    assert_eq!(parse_wkt("srid=3857;LINESTRING (0 0, 1 1)").unwrap().segments.len(), 1);
+    assert!(parse_wkt("LINESTRING (0 0, 1 1) SRI").is_err());
+    assert!(parse_wkt("LINESTRING (0 0, 1 1) ééé").is_err());
+}